@@ -0,0 +1,188 @@
+use std::fmt;
+
+/// Unsigned arbitrary-precision integer used by `LiteralKind::BigInt`
+///
+/// Stored as base 2^32 limbs, least significant limb first, without
+/// trailing zero limbs so that derived equality compares values.
+#[derive(Clone, PartialEq, Eq)]
+pub struct BigUint {
+    limbs: Vec<u32>,
+}
+
+impl BigUint {
+    pub fn zero() -> BigUint {
+        BigUint { limbs: Vec::new() }
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.limbs.is_empty()
+    }
+
+    /// Parses digits without prefix, sign or separators
+    pub fn from_str_radix(digits: &str, radix: u32) -> Option<BigUint> {
+        if digits.is_empty() {
+            return None;
+        }
+        let mut number = BigUint::zero();
+        for ch in digits.chars() {
+            let digit = ch.to_digit(radix)?;
+            number.mul_add(radix, digit);
+        }
+        Some(number)
+    }
+
+    // self = self * factor + addend
+    fn mul_add(&mut self, factor: u32, addend: u32) {
+        let mut carry = addend as u64;
+        for limb in self.limbs.iter_mut() {
+            let value = *limb as u64 * factor as u64 + carry;
+            *limb = value as u32;
+            carry = value >> 32;
+        }
+        if carry != 0 {
+            self.limbs.push(carry as u32);
+        }
+    }
+
+    // self = self / divisor, returning the remainder
+    fn div_rem(&mut self, divisor: u32) -> u32 {
+        let mut remainder = 0u64;
+        for limb in self.limbs.iter_mut().rev() {
+            let value = remainder << 32 | *limb as u64;
+            *limb = (value / divisor as u64) as u32;
+            remainder = value % divisor as u64;
+        }
+        while self.limbs.last() == Some(&0) {
+            self.limbs.pop();
+        }
+        remainder as u32
+    }
+}
+
+impl fmt::Display for BigUint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_zero() {
+            return f.write_str("0");
+        }
+        // peel off 9 decimal digits at a time, least significant chunk first
+        const CHUNK: u32 = 1_000_000_000;
+        let mut number = self.clone();
+        let mut chunks = Vec::new();
+        while !number.is_zero() {
+            chunks.push(number.div_rem(CHUNK));
+        }
+        let mut chunks = chunks.iter().rev();
+        write!(f, "{}", chunks.next().unwrap())?;
+        for chunk in chunks {
+            write!(f, "{:09}", chunk)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Debug for BigUint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(self, f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::BigUint;
+    use crate::error_codes::ErrorCode;
+    use crate::token::{Base, LiteralKind, TokenKind};
+    use crate::tokenize;
+
+    fn decimal(digits: &str) -> String {
+        BigUint::from_str_radix(digits, 10).unwrap().to_string()
+    }
+
+    #[test]
+    fn zero() {
+        assert!(BigUint::from_str_radix("0", 10).unwrap().is_zero());
+        assert_eq!(BigUint::from_str_radix("000", 16), Some(BigUint::zero()));
+        assert_eq!(BigUint::zero().to_string(), "0");
+        assert_eq!(BigUint::from_str_radix("", 10), None);
+
+        let (tokens, diagnostics) = tokenize("0n");
+        assert!(diagnostics.is_empty());
+        assert_eq!(
+            tokens[0].kind,
+            TokenKind::Literal {
+                kind: LiteralKind::BigInt {
+                    base: Base::Decimal,
+                    value: BigUint::zero(),
+                },
+            }
+        );
+    }
+
+    #[test]
+    fn leading_zeros_do_not_change_the_value() {
+        assert_eq!(decimal("000123"), "123");
+        assert_eq!(
+            BigUint::from_str_radix("00000000000000000000000001", 10),
+            BigUint::from_str_radix("1", 10)
+        );
+    }
+
+    #[test]
+    fn limb_boundaries() {
+        for value in [
+            u32::MAX as u128,
+            1 << 32,
+            (1 << 32) + 1,
+            u64::MAX as u128,
+            1 << 64,
+            (1 << 64) + 1,
+            u128::MAX,
+        ] {
+            assert_eq!(decimal(&value.to_string()), value.to_string());
+            assert_eq!(
+                BigUint::from_str_radix(&format!("{:x}", value), 16)
+                    .unwrap()
+                    .to_string(),
+                value.to_string()
+            );
+        }
+    }
+
+    #[test]
+    fn hexadecimal() {
+        let value = BigUint::from_str_radix("DEADbeefCAFEbabe0123456789", 16).unwrap();
+        assert_eq!(
+            value.to_string(),
+            0xDEADBEEFCAFEBABE0123456789_u128.to_string()
+        );
+        assert_eq!(BigUint::from_str_radix("fg", 16), None);
+    }
+
+    #[test]
+    fn chunks_keep_their_inner_zeros() {
+        assert_eq!(decimal("1000000000000000001"), "1000000000000000001");
+        assert_eq!(decimal("1000000000"), "1000000000");
+        assert_eq!(
+            decimal("1000000000000000000000000000"),
+            "1000000000000000000000000000"
+        );
+        assert_eq!(decimal("99999999999999999999"), "99999999999999999999");
+
+        let (tokens, diagnostics) = tokenize("1000000000000000001n");
+        assert!(diagnostics.is_empty());
+        let TokenKind::Literal {
+            kind: LiteralKind::BigInt { value, .. },
+        } = &tokens[0].kind
+        else {
+            panic!("{:?}", tokens);
+        };
+        assert_eq!(value.to_string(), "1000000000000000001");
+    }
+
+    #[test]
+    fn int_without_big_suffix_overflows() {
+        let (tokens, diagnostics) = tokenize("99999999999999999999");
+        assert_eq!(tokens.len(), 1);
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].code, ErrorCode::IntOverflow);
+    }
+}
@@ -0,0 +1,161 @@
+use std::collections::{HashMap, HashSet};
+
+use unicode_security::{skeleton, MixedScript};
+
+use crate::diagnostic::{Diagnostic, Label};
+use crate::error_codes::ErrorCode;
+use crate::token::Span;
+
+/// Warns about identifiers mixing scripts or looking like another
+/// identifier of the file, as described by UTS #39
+///
+/// Two identifiers look alike when their confusable skeletons are equal.
+/// Identifiers which are both ascii are never reported, since pairs like
+/// "l1" and "ll" are just as easy to tell apart as to mistype.
+#[derive(Debug, Default)]
+pub(crate) struct IdentLint {
+    // every distinct identifier is only checked the first time it is seen
+    seen: HashSet<String>,
+    // first identifier seen with each skeleton, and where
+    skeletons: HashMap<String, (String, Span)>,
+    // most distinct identifiers remembered, the ones after are still checked
+    // but every time they are used
+    limit: Option<usize>,
+}
+
+impl IdentLint {
+    pub(crate) fn with_limit(limit: usize) -> IdentLint {
+        IdentLint {
+            limit: Some(limit),
+            ..IdentLint::default()
+        }
+    }
+
+    pub(crate) fn check(&mut self, name: &str, span: Span) -> Vec<Diagnostic> {
+        let mut diagnostics = Vec::new();
+        if self.seen.contains(name) {
+            return diagnostics;
+        }
+        let full = self.limit.is_some_and(|limit| self.seen.len() >= limit);
+        if !full {
+            self.seen.insert(name.to_string());
+        }
+        if !name.is_ascii() && !name.is_single_script() {
+            diagnostics.push(
+                Diagnostic::warning(
+                    ErrorCode::MixedScriptIdent,
+                    "Identifier mixes scripts",
+                    Label::new(span, format!("`{}` mixes letters of several scripts", name)),
+                )
+                .with_note("letters of other scripts can look the same as `latin` ones"),
+            );
+        }
+        let skeleton: String = skeleton(name).collect();
+        match self.skeletons.get(&skeleton) {
+            Some((other, other_span)) if !(name.is_ascii() && other.is_ascii()) => {
+                diagnostics.push(
+                    Diagnostic::warning(
+                        ErrorCode::ConfusableIdent,
+                        "Confusable identifiers",
+                        Label::new(span, format!("`{}` looks like `{}`", name, other)),
+                    )
+                    .with_secondary(Label::new(*other_span, format!("`{}` is used here", other)))
+                    .with_note("these identifiers are different but are hard to tell apart"),
+                );
+            }
+            Some(_) => {}
+            None if !full => {
+                self.skeletons.insert(skeleton, (name.to_string(), span));
+            }
+            None => {}
+        }
+        diagnostics
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::IdentLint;
+    use crate::error_codes::ErrorCode;
+    use crate::source_map::FileId;
+    use crate::token::{Span, TokenKind};
+    use crate::tokenize;
+
+    fn codes(source: &str) -> Vec<ErrorCode> {
+        let (_, diagnostics) = tokenize(source);
+        diagnostics
+            .iter()
+            .map(|diagnostic| diagnostic.code)
+            .collect()
+    }
+
+    #[test]
+    fn identifiers_are_nfc_normalized() {
+        // precomposed "é" and "e" followed by a combining acute accent
+        let (tokens, diagnostics) = tokenize("café cafe\u{301}");
+        assert!(diagnostics.is_empty());
+        assert_eq!(tokens[0].kind, tokens[2].kind);
+        assert_eq!(
+            tokens[2].kind,
+            TokenKind::Ident {
+                name: "café".into()
+            }
+        );
+        // the span still covers the characters as written
+        assert_eq!(tokens[2].span.len(), 5);
+    }
+
+    #[test]
+    fn confusable_identifiers_are_reported_once() {
+        let (_, diagnostics) = tokenize("let cop = 1;\nlet сор = cop + сор;");
+        assert_eq!(diagnostics.len(), 1);
+        let diagnostic = &diagnostics[0];
+        assert_eq!(diagnostic.code, ErrorCode::ConfusableIdent);
+        assert_eq!(
+            diagnostic.primary.span,
+            Span::set(FileId::default(), 17, 20)
+        );
+        assert_eq!(
+            diagnostic.secondary[0].span,
+            Span::set(FileId::default(), 4, 7)
+        );
+
+        // both ascii, so told apart by reading them
+        assert_eq!(codes("let rn = m;"), []);
+    }
+
+    #[test]
+    fn mixed_script_identifiers_are_reported() {
+        // cyrillic 'а' in a latin word
+        assert_eq!(codes("let pаth = 1;"), [ErrorCode::MixedScriptIdent]);
+        assert_eq!(codes("let 東京tokyo = 1;"), [ErrorCode::MixedScriptIdent]);
+        // the scripts japanese and korean are written in combine
+        assert_eq!(codes("let ひらがなカタカナ漢字 = 1; let 한글漢字 = 2;"), []);
+        assert_eq!(codes("let путь = 1; let path = 2;"), []);
+    }
+
+    #[test]
+    fn only_identifiers_are_checked() {
+        let sources = [
+            "// pаth сор\nlet cop = 1;",
+            "/* pаth */ let cop = \"сор\";",
+            "let cop = r#\"pаth сор\"#;",
+            "let cop = \"\"\"\n  pаth сор\n\"\"\";",
+            "let cop = `pаth ${1} сор`;",
+        ];
+        for source in sources {
+            assert_eq!(codes(source), [], "{}", source);
+        }
+    }
+
+    #[test]
+    fn identifiers_after_the_limit_are_still_checked() {
+        let mut lint = IdentLint::with_limit(1);
+        let span = Span::set(FileId::default(), 0, 3);
+        assert!(lint.check("cop", span).is_empty());
+        // not remembered, so reported on every use
+        assert_eq!(lint.check("сор", span).len(), 1);
+        assert_eq!(lint.check("сор", span).len(), 1);
+        assert_eq!(lint.check("pаth", span).len(), 1);
+    }
+}
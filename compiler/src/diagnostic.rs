@@ -0,0 +1,415 @@
+use crate::error_codes::ErrorCode;
+use crate::token::{LiteralKind, Span, StrForm, Suffix, Token, TokenKind};
+
+/// Problem found in the source, independent of how it gets rendered
+///
+/// Words wrapped in backticks inside messages and notes are the ones a
+/// renderer may highlight, the backticks themselves are not part of the text.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub code: ErrorCode,
+    pub message: String,
+    /// label pointing at the offending source
+    pub primary: Label,
+    /// labels giving more context, e.g. where a delimiter was opened
+    pub secondary: Vec<Label>,
+    pub notes: Vec<String>,
+    /// mechanical fixes, applied by the `fix` command
+    pub suggestions: Vec<Suggestion>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+/// How a lint is reported, or not at all
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LintLevel {
+    Allow,
+    Warn,
+    Deny,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Label {
+    pub span: Span,
+    pub message: String,
+}
+
+impl Label {
+    pub fn new(span: Span, message: impl Into<String>) -> Label {
+        Label {
+            span,
+            message: message.into(),
+        }
+    }
+}
+
+/// Replaces the source in span with the replacement, an empty span inserts it
+#[derive(Debug, Clone, PartialEq)]
+pub struct Suggestion {
+    pub span: Span,
+    pub replacement: String,
+    pub message: String,
+}
+
+impl Suggestion {
+    pub fn new(
+        span: Span,
+        replacement: impl Into<String>,
+        message: impl Into<String>,
+    ) -> Suggestion {
+        Suggestion {
+            span,
+            replacement: replacement.into(),
+            message: message.into(),
+        }
+    }
+
+    /// Inserts the text right after the span
+    pub fn insert_after(
+        span: Span,
+        text: impl Into<String>,
+        message: impl Into<String>,
+    ) -> Suggestion {
+        Suggestion::new(Span::set(span.file, span.high, span.high), text, message)
+    }
+}
+
+/// Renders diagnostics somewhere, e.g. as coloured reports on stderr
+pub trait Emitter {
+    fn emit(&mut self, diagnostic: &Diagnostic);
+}
+
+impl Diagnostic {
+    pub fn error(code: ErrorCode, message: impl Into<String>, primary: Label) -> Diagnostic {
+        Diagnostic {
+            severity: Severity::Error,
+            code,
+            message: message.into(),
+            primary,
+            secondary: Vec::new(),
+            notes: Vec::new(),
+            suggestions: Vec::new(),
+        }
+    }
+
+    pub fn warning(code: ErrorCode, message: impl Into<String>, primary: Label) -> Diagnostic {
+        Diagnostic {
+            severity: Severity::Warning,
+            ..Diagnostic::error(code, message, primary)
+        }
+    }
+
+    pub fn with_secondary(mut self, label: Label) -> Diagnostic {
+        self.secondary.push(label);
+        self
+    }
+
+    pub fn with_note(mut self, note: impl Into<String>) -> Diagnostic {
+        self.notes.push(note.into());
+        self
+    }
+
+    pub fn with_suggestion(mut self, suggestion: Suggestion) -> Diagnostic {
+        self.suggestions.push(suggestion);
+        self
+    }
+
+    /// Diagnostic for a bidi control character at offset inside the token
+    pub fn bidi_control(token: &Token, offset: usize, ch: char) -> Diagnostic {
+        let place = match &token.kind {
+            TokenKind::Shebang
+            | TokenKind::LineComment
+            | TokenKind::BlockComment { .. }
+            | TokenKind::LineDocComment { .. }
+            | TokenKind::BlockDocComment { .. } => BidiPlace::Comment,
+            TokenKind::Literal {
+                kind:
+                    LiteralKind::Str {
+                        form: StrForm::Raw { .. },
+                        ..
+                    },
+            }
+            | TokenKind::MismatchedRawStr { .. } => BidiPlace::RawString,
+            TokenKind::Literal {
+                kind:
+                    LiteralKind::Str {
+                        form: StrForm::MultiLine,
+                        ..
+                    },
+            } => BidiPlace::MultiLineString,
+            TokenKind::Literal {
+                kind: LiteralKind::Str { .. },
+            }
+            | TokenKind::Template { .. }
+            | TokenKind::InvalidEscape { .. } => BidiPlace::String,
+            _ => BidiPlace::Code,
+        };
+        let span = Span::set(token.span.file, offset, offset + 1);
+        let code_point = format!("U+{:04X}", ch as u32);
+        let diagnostic = Diagnostic::error(
+            ErrorCode::BidiControl,
+            "Unicode bidirectional control character",
+            Label::new(
+                span,
+                format!("`{}` {} in {}", code_point, bidi_name(ch), place.name()),
+            ),
+        )
+        .with_note(
+            "bidirectional controls change the order text is displayed in, so the code may read differently from how it runs",
+        );
+        match place {
+            // these strings have no escapes, and their value would change
+            BidiPlace::RawString | BidiPlace::MultiLineString => diagnostic,
+            BidiPlace::String => diagnostic.with_suggestion(Suggestion::new(
+                span,
+                format!("\\u{{{:X}}}", ch as u32),
+                "write it as an `escape` if it is intended",
+            )),
+            BidiPlace::Comment => diagnostic.with_suggestion(Suggestion::new(
+                span,
+                "",
+                "remove the `invisible` character",
+            )),
+            // removing it could join the tokens around it into one
+            BidiPlace::Code => diagnostic.with_suggestion(Suggestion::new(
+                span,
+                " ",
+                "separate the tokens with a `space` instead",
+            )),
+        }
+    }
+
+    /// Diagnostic for an error token, None for every other token
+    pub fn from_token(token: &Token) -> Option<Diagnostic> {
+        let span = token.span;
+        // span of length characters at offset from the start of the token
+        let at = |offset: usize, length: usize| {
+            Span::set(span.file, span.low + offset, span.low + offset + length)
+        };
+        let diagnostic = match token.kind {
+            TokenKind::InvalidIdent => Diagnostic::error(
+                ErrorCode::InvalidIdent,
+                "Invalid Identifier",
+                Label::new(span, "This is an `invalid` identifier name"),
+            )
+            .with_note(
+                "Identifier names must be made of `ascii` or `unicode` characters which are not `emoji`",
+            ),
+            TokenKind::InvalidDecimal => Diagnostic::error(
+                ErrorCode::InvalidDecimal,
+                "Invalid Floating Point",
+                Label::new(span, "No digits found after `decimal` point"),
+            )
+            .with_note("Atleast one `digit` must be present after `decimal` point")
+            .with_suggestion(Suggestion::insert_after(
+                span,
+                "0",
+                "add a `digit` after the `decimal` point",
+            )),
+            TokenKind::InvalidExponent => Diagnostic::error(
+                ErrorCode::InvalidExponent,
+                "Invalid Exponent",
+                Label::new(span, "No digits found after `exponent`"),
+            )
+            .with_note("Atleast one `digit` must be present after `exponent`")
+            .with_suggestion(Suggestion::insert_after(
+                span,
+                "0",
+                "add a `digit` after the `exponent`",
+            )),
+            TokenKind::BlockComment { terminated: false }
+            | TokenKind::BlockDocComment {
+                terminated: false, ..
+            } => Diagnostic::error(
+                ErrorCode::UnterminatedBlockComment,
+                "Unterminated Block Comment",
+                Label::new(span, "block `comment` is unterminated"),
+            )
+            .with_note("block `comment` must be terminated with `*/`")
+            .with_suggestion(Suggestion::insert_after(
+                span,
+                "*/",
+                "close the block `comment`",
+            )),
+            TokenKind::Literal {
+                kind:
+                    LiteralKind::Str {
+                        terminated: false,
+                        start,
+                        form,
+                        ..
+                    },
+            } => Diagnostic::error(
+                ErrorCode::UnterminatedStr,
+                "Unterminated string",
+                Label::new(span, "This `string` is unterminated"),
+            )
+            .with_note(format!(
+                "`string` must be terminated with `{}`",
+                form.terminator(start)
+            ))
+            .with_suggestion(Suggestion::insert_after(
+                span,
+                form.terminator(start),
+                "close the `string`",
+            )),
+            TokenKind::Unknown => Diagnostic::error(
+                ErrorCode::UnknownToken,
+                "Unknown Token",
+                Label::new(span, "This is an `unknown` token"),
+            )
+            .with_note("This `token` does not belong to the `language`"),
+            TokenKind::EmptyInt { base } => Diagnostic::error(
+                ErrorCode::EmptyInt,
+                "Empty Integer Literal",
+                Label::new(
+                    span,
+                    format!("No digits found after `{}` prefix", base.name()),
+                ),
+            )
+            .with_note(format!(
+                "Atleast one `{}` digit must be present after the `prefix`",
+                base.name()
+            )),
+            TokenKind::InvalidDigit { base, offset } => Diagnostic::error(
+                ErrorCode::InvalidDigit,
+                "Invalid Digit",
+                Label::new(
+                    at(offset, 1),
+                    format!("This digit is invalid in a `{}` literal", base.name()),
+                ),
+            )
+            .with_note(format!(
+                "`{}` literals may only contain digits below `{}`",
+                base.name(),
+                base as u32
+            )),
+            TokenKind::InvalidSuffix { offset } => Diagnostic::error(
+                ErrorCode::InvalidSuffix,
+                "Invalid Suffix",
+                Label::new(
+                    Span::set(span.file, span.low + offset, span.high),
+                    "This is an `invalid` literal suffix",
+                ),
+            )
+            .with_note(
+                "`u`, `i` and `n` can suffix any integer, `f` only decimal integers and floats",
+            ),
+            TokenKind::InvalidSeparator { offset } => Diagnostic::error(
+                ErrorCode::InvalidSeparator,
+                "Misplaced Digit Separator",
+                Label::new(at(offset, 1), "This `separator` is misplaced"),
+            )
+            .with_note("`_` may only be placed between two `digits`"),
+            TokenKind::IntOverflow { suffix } => {
+                let (article, name, max) = match suffix {
+                    Some(Suffix::Unsigned) => ("a", "uint", u64::MAX.to_string()),
+                    _ => ("an", "int", isize::MAX.to_string()),
+                };
+                Diagnostic::error(
+                    ErrorCode::IntOverflow,
+                    "Integer Literal Too Large",
+                    Label::new(span, format!("This `integer` does not fit in {} {}", article, name)),
+                )
+                .with_note(format!(
+                    "the largest `{}` is `{}`, use the `n` suffix for an arbitrary-precision integer",
+                    name, max
+                ))
+            }
+            TokenKind::InvalidEscape { offset, length } => Diagnostic::error(
+                ErrorCode::InvalidEscape,
+                "Invalid Escape Sequence",
+                Label::new(at(offset, length), "This `escape sequence` is invalid"),
+            )
+            .with_note(
+                "valid escapes are `\\n \\t \\r \\0 \\\\ \\\" \\'`, `\\xNN` up to 7F and `\\u{...}` with one to six hex digits",
+            ),
+            TokenKind::Template {
+                terminated: false, ..
+            } => Diagnostic::error(
+                ErrorCode::UnterminatedTemplate,
+                "Unterminated template string",
+                Label::new(span, "This `template string` is unterminated"),
+            )
+            .with_note("`template string` must be terminated with a `backtick`")
+            .with_suggestion(Suggestion::insert_after(
+                span,
+                "`",
+                "close the `template string`",
+            )),
+            TokenKind::UnterminatedInterpolation => Diagnostic::error(
+                ErrorCode::UnterminatedInterpolation,
+                "Unterminated interpolation",
+                Label::new(span, "This `interpolation` is never closed"),
+            )
+            .with_note("`${` must be closed with `}` before the template string continues"),
+            TokenKind::MismatchedRawStr {
+                expected,
+                found,
+                offset,
+            } => Diagnostic::error(
+                ErrorCode::MismatchedRawStr,
+                "Mismatched raw string delimiter",
+                Label::new(
+                    at(offset, 1 + found),
+                    format!(
+                        "This closes with `{}` `#` but the raw string opened with `{}`",
+                        found, expected
+                    ),
+                ),
+            )
+            .with_secondary(Label::new(at(0, 2 + expected), "raw string opened here"))
+            .with_note(
+                "`raw string` must be closed with `\"` followed by the same number of `#` it opened with",
+            ),
+            _ => return None,
+        };
+        Some(diagnostic)
+    }
+}
+
+// Kind of token a bidi control character is found in
+#[derive(Clone, Copy)]
+enum BidiPlace {
+    Comment,
+    /// a string with escape sequences
+    String,
+    RawString,
+    MultiLineString,
+    Code,
+}
+
+impl BidiPlace {
+    fn name(self) -> &'static str {
+        match self {
+            BidiPlace::Comment => "a `comment`",
+            BidiPlace::String => "a `string`",
+            BidiPlace::RawString => "a `raw string`",
+            BidiPlace::MultiLineString => "a `multi-line string`",
+            BidiPlace::Code => "`code`",
+        }
+    }
+}
+
+fn bidi_name(ch: char) -> &'static str {
+    match ch {
+        '\u{061C}' => "ARABIC LETTER MARK",
+        '\u{200E}' => "LEFT-TO-RIGHT MARK",
+        '\u{200F}' => "RIGHT-TO-LEFT MARK",
+        '\u{202A}' => "LEFT-TO-RIGHT EMBEDDING",
+        '\u{202B}' => "RIGHT-TO-LEFT EMBEDDING",
+        '\u{202C}' => "POP DIRECTIONAL FORMATTING",
+        '\u{202D}' => "LEFT-TO-RIGHT OVERRIDE",
+        '\u{202E}' => "RIGHT-TO-LEFT OVERRIDE",
+        '\u{2066}' => "LEFT-TO-RIGHT ISOLATE",
+        '\u{2067}' => "RIGHT-TO-LEFT ISOLATE",
+        '\u{2068}' => "FIRST STRONG ISOLATE",
+        '\u{2069}' => "POP DIRECTIONAL ISOLATE",
+        _ => "bidirectional control",
+    }
+}
@@ -1,144 +1,106 @@
-use super::token::Token;
-use crate::{token::LiteralKind, TokenKind};
-use ariadne::{Color, ColorGenerator, Fmt, Label, Report, ReportKind, Source};
+use crate::diagnostic::{Diagnostic, Emitter, Severity};
+use crate::source_map::SourceMap;
+use crate::token::Span;
+use ariadne::{Color, ColorGenerator, Fmt, Label, Report, ReportKind};
 
+/// Renders diagnostics as coloured ariadne reports on stderr
 #[derive(Debug)]
 pub(crate) struct LErrorHandler<'a> {
-    code: &'a str,
-    file_name: &'a str,
+    source_map: &'a SourceMap,
 }
 
 impl<'a> LErrorHandler<'a> {
-    pub(crate) fn new(code: &'a str, file_name: &'a str) -> LErrorHandler<'a> {
-        LErrorHandler { code, file_name }
+    pub(crate) fn new(source_map: &'a SourceMap) -> LErrorHandler<'a> {
+        LErrorHandler { source_map }
     }
 
-    pub(crate) fn error_report(&self, err_token: &Token) {
+    // ariadne identifies sources by file name, its source cache needs them owned
+    fn location(&self, span: Span) -> (String, std::ops::Range<usize>) {
+        let name = self.source_map.file(span.file).name.clone();
+        (name, span.low..span.high)
+    }
+
+    pub(crate) fn error_report(&self, diagnostic: &Diagnostic) {
         let mut colors = ColorGenerator::new();
 
         // Generate & choose some colours for each of our elements
         let a = colors.next();
         let out = Color::Fixed(81);
 
-        match err_token.kind {
-            TokenKind::InvalidIdent => {
-                Report::build(ReportKind::Error, self.file_name, err_token.span.low)
-                    .with_code(1)
-                    .with_message(format!("Invalid Identifier"))
-                    .with_label(
-                        Label::new((self.file_name, err_token.span.low..err_token.span.high))
-                            .with_message(format!("This is an {} identifier name", "invalid".fg(a)))
-                            .with_color(a),
-                    )
-                    .with_note(format!(
-                        "Identifier names must be made of {} or {} characters which are not {}",
-                        "ascii".fg(out),
-                        "unicode".fg(out),
-                        "emoji".fg(out)
-                    ))
-                    .finish()
-                    .print((self.file_name, Source::from(self.code)))
-                    .unwrap()
-            }
-            TokenKind::InvalidDecimal => {
-                Report::build(ReportKind::Error, self.file_name, err_token.span.low)
-                    .with_code(2)
-                    .with_message(format!("Invalid Floating Point"))
-                    .with_label(
-                        Label::new((self.file_name, err_token.span.low..err_token.span.high))
-                            .with_message(format!(
-                                "No digits found after {} point",
-                                "decimal".fg(a)
-                            ))
-                            .with_color(a),
-                    )
-                    .with_note(format!(
-                        "Atleast one {} must be present after {} point",
-                        "digit".fg(out),
-                        "decimal".fg(out)
-                    ))
-                    .finish()
-                    .print((self.file_name, Source::from(self.code)))
-                    .unwrap()
-            }
-            TokenKind::InvalidExponent => {
-                Report::build(ReportKind::Error, self.file_name, err_token.span.low)
-                    .with_code(3)
-                    .with_message(format!("Invalid Exponent"))
-                    .with_label(
-                        Label::new((self.file_name, err_token.span.low..err_token.span.high))
-                            .with_message(format!("No digits found after {}", "exponent".fg(a)))
-                            .with_color(a),
-                    )
-                    .with_note(format!(
-                        "Atleast one {} must be present after {}",
-                        "digit".fg(out),
-                        "exponent".fg(out)
-                    ))
-                    .finish()
-                    .print((self.file_name, Source::from(self.code)))
-                    .unwrap()
-            }
-            TokenKind::BlockComment { terminated: false } => {
-                Report::build(ReportKind::Error, self.file_name, err_token.span.low)
-                    .with_code(4)
-                    .with_message(format!("Unterminated Block Comment"))
-                    .with_label(
-                        Label::new((self.file_name, err_token.span.low..err_token.span.high))
-                            .with_message(format!("block {} is unterminated", "comment".fg(a)))
-                            .with_color(a),
-                    )
-                    .with_note(format!(
-                        "block {} must be terminated with {}",
-                        "comment".fg(out),
-                        "*/".fg(out)
-                    ))
-                    .finish()
-                    .print((self.file_name, Source::from(self.code)))
-                    .unwrap()
-            }
-            TokenKind::Literal {
-                kind:
-                    LiteralKind::Str {
-                        terminated: false,
-                        start: character,
-                        value: _,
-                    },
-            } => Report::build(ReportKind::Error, self.file_name, err_token.span.low)
-                .with_code(5)
-                .with_message(format!("Unterminated string"))
-                .with_label(
-                    Label::new((self.file_name, err_token.span.low..err_token.span.high - 1))
-                        .with_message(format!("This {} is unterminated", "string".fg(a)))
-                        .with_color(a),
+        let kind = match diagnostic.severity {
+            Severity::Error => ReportKind::Error,
+            Severity::Warning => ReportKind::Warning,
+        };
+        let primary = &diagnostic.primary;
+        let (file_name, _) = self.location(primary.span);
+        let mut report = Report::build(kind, file_name, primary.span.low)
+            .with_code(diagnostic.code)
+            .with_message(&diagnostic.message)
+            .with_label(
+                Label::new(self.location(primary.span))
+                    .with_message(highlight(&primary.message, a))
+                    .with_color(a),
+            );
+        for label in &diagnostic.secondary {
+            let color = colors.next();
+            report = report.with_label(
+                Label::new(self.location(label.span))
+                    .with_message(highlight(&label.message, color))
+                    .with_color(color),
+            );
+        }
+        for note in &diagnostic.notes {
+            report = report.with_note(highlight(note, out));
+        }
+        // ariadne shows a single help, so every suggestion goes on its own line
+        let help = diagnostic
+            .suggestions
+            .iter()
+            .map(|suggestion| {
+                let message = highlight(&suggestion.message, out);
+                // a removal says all there is to say in its message
+                if suggestion.replacement.is_empty() {
+                    return message;
+                }
+                let verb = match suggestion.span.low == suggestion.span.high {
+                    true => "insert",
+                    false => "replace with",
+                };
+                format!(
+                    "{}, {} {}",
+                    message,
+                    verb,
+                    suggestion.replacement.as_str().fg(a)
                 )
-                .with_note(format!(
-                    "{} must be terminated with {}",
-                    "string".fg(out),
-                    character.fg(out)
-                ))
-                .finish()
-                .print((self.file_name, Source::from(self.code)))
-                .unwrap(),
-            TokenKind::Unknown => {
-                Report::build(ReportKind::Error, self.file_name, err_token.span.low)
-                    .with_code(6)
-                    .with_message(format!("Unknown Token"))
-                    .with_label(
-                        Label::new((self.file_name, err_token.span.low..err_token.span.high))
-                            .with_message(format!("This is an {} token", "unknown".fg(a)))
-                            .with_color(a),
-                    )
-                    .with_note(format!(
-                        "This {} does not belong to the {}",
-                        "token".fg(out),
-                        "language".fg(out)
-                    ))
-                    .finish()
-                    .print((self.file_name, Source::from(self.code)))
-                    .unwrap()
-            }
-            _ => {}
+            })
+            .collect::<Vec<_>>();
+        if !help.is_empty() {
+            report = report.with_help(help.join("\n"));
         }
+        report
+            .finish()
+            .print(ariadne::sources(
+                self.source_map
+                    .files()
+                    .map(|(_, file)| (file.name.clone(), file.source.as_str())),
+            ))
+            .unwrap()
     }
 }
+
+impl Emitter for LErrorHandler<'_> {
+    fn emit(&mut self, diagnostic: &Diagnostic) {
+        self.error_report(diagnostic);
+    }
+}
+
+/// Colours the words wrapped in backticks and drops the backticks
+fn highlight(text: &str, color: Color) -> String {
+    text.split('`')
+        .enumerate()
+        .map(|(i, part)| match i % 2 {
+            0 => part.to_string(),
+            _ => part.fg(color).to_string(),
+        })
+        .collect()
+}
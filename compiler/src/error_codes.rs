@@ -0,0 +1,280 @@
+use std::fmt;
+
+/// Long form documentation of an error code, shown by `--explain`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Explanation {
+    pub description: &'static str,
+    /// source producing the error
+    pub erroneous: &'static str,
+    /// the same source with the error fixed
+    pub corrected: &'static str,
+}
+
+// Declares every code together with its explanation, so a code can not be
+// added without one
+macro_rules! error_codes {
+    ($($(#[$meta:meta])* $name:ident = $number:literal => $explanation:expr,)*) => {
+        /// Stable code of a diagnostic, displayed as `E` followed by four digits
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+        pub enum ErrorCode {
+            $($(#[$meta])* $name = $number,)*
+        }
+
+        impl ErrorCode {
+            pub const ALL: &'static [ErrorCode] = &[$(ErrorCode::$name,)*];
+
+            pub fn explanation(&self) -> Explanation {
+                match self {
+                    $(ErrorCode::$name => $explanation,)*
+                }
+            }
+        }
+    };
+}
+
+error_codes! {
+    InvalidIdent = 1 => Explanation {
+        description: "\
+An identifier contains a character that can not be part of a name.
+
+Identifiers start with a letter or `_` and continue with letters, digits and
+`_`, where letters are any unicode characters with the XID properties. Emoji
+and other symbols are not letters, so they can not appear in names.",
+        erroneous: "let total😀 = 1;",
+        corrected: "let total_smile = 1;",
+    },
+    InvalidDecimal = 2 => Explanation {
+        description: "\
+A float literal ends with its decimal point.
+
+At least one digit has to follow the `.` of a float literal, write `1.0`
+rather than `1.`. A `.` followed by another `.` or by a name is not part of
+the literal, so `1..10` is a range and `5.abs()` a method call.",
+        erroneous: "let half = 1. / 2;",
+        corrected: "let half = 1.0 / 2;",
+    },
+    InvalidExponent = 3 => Explanation {
+        description: "\
+A float literal has an exponent marker with no digits after it.
+
+The `e` or `E` of a float literal, optionally followed by a `+` or `-` sign,
+has to be followed by at least one digit.",
+        erroneous: "let big = 1e;",
+        corrected: "let big = 1e0;",
+    },
+    UnterminatedBlockComment = 4 => Explanation {
+        description: "\
+A block comment or block doc comment is never closed.
+
+Block comments opened with `/*` run until the matching `*/`. They nest, so
+every `/*` inside the comment needs its own `*/` as well.",
+        erroneous: "/* outer /* inner */ still outer",
+        corrected: "/* outer /* inner */ still outer */",
+    },
+    UnterminatedStr = 5 => Explanation {
+        description: "\
+A string literal is never closed.
+
+Quoted strings end with the quote they started with before the end of their
+line, raw strings with `\"` followed by as many `#` as they opened with, and
+multi-line strings with `\"\"\"`. An escaped quote such as `\\\"` does not
+end a quoted string, use a multi-line string for text spanning lines.",
+        erroneous: "let name = \"rustscript;",
+        corrected: "let name = \"rustscript\";",
+    },
+    UnknownToken = 6 => Explanation {
+        description: "\
+A character that is not part of the language appears outside of a comment
+or string.",
+        erroneous: "let cost = 5 € 2;",
+        corrected: "let cost = 5 * 2;",
+    },
+    EmptyInt = 7 => Explanation {
+        description: "\
+An integer literal has a base prefix with no digits after it.
+
+The `0b`, `0o` and `0x` prefixes have to be followed by at least one digit of
+their base.",
+        erroneous: "let mask = 0x;",
+        corrected: "let mask = 0xFF;",
+    },
+    InvalidDigit = 8 => Explanation {
+        description: "\
+An integer literal contains a digit that is too large for its base.
+
+Binary literals may only contain `0` and `1`, octal literals the digits `0`
+to `7`.",
+        erroneous: "let flags = 0b102;",
+        corrected: "let flags = 0b101;",
+    },
+    InvalidSuffix = 9 => Explanation {
+        description: "\
+A number literal ends with an unknown suffix.
+
+Integers may be suffixed with `u`, `i` or `n`, decimal integers and floats
+with `f`. A `_` between the digits and a valid suffix, as in `12_u`, is a
+misplaced digit separator (E0010) rather than part of the suffix.",
+        erroneous: "let size = 10kb;",
+        corrected: "let size = 10u;",
+    },
+    InvalidSeparator = 10 => Explanation {
+        description: "\
+A digit separator is not placed between two digits.
+
+`_` may be used to group the digits of a number literal, but it can not
+start or end the digits, be repeated, or touch a prefix, decimal point or
+exponent.",
+        erroneous: "let million = 1__000_000_;",
+        corrected: "let million = 1_000_000;",
+    },
+    IntOverflow = 11 => Explanation {
+        description: "\
+An integer literal is too large to fit in an int.
+
+Integers range up to the largest 64-bit signed integer, or the largest
+64-bit unsigned integer with the `u` suffix. Use the `n` suffix to make it
+an arbitrary-precision integer instead.",
+        erroneous: "let huge = 99999999999999999999;",
+        corrected: "let huge = 99999999999999999999n;",
+    },
+    InvalidEscape = 12 => Explanation {
+        description: "\
+A string contains an unknown or malformed escape sequence.
+
+The valid escapes are `\\n`, `\\t`, `\\r`, `\\0`, `\\\\`, `\\\"` and `\\'`,
+`\\xNN` with two hex digits up to `7F`, and `\\u{...}` with one to six hex
+digits naming a unicode scalar value. Raw strings do not process escapes.",
+        erroneous: "let path = \"C:\\dir\";",
+        corrected: "let path = \"C:\\\\dir\";",
+    },
+    UnterminatedTemplate = 13 => Explanation {
+        description: "\
+A template string is never closed with a backtick.",
+        erroneous: "let greeting = `hello ${name}!;",
+        corrected: "let greeting = `hello ${name}!`;",
+    },
+    UnterminatedInterpolation = 14 => Explanation {
+        description: "\
+An interpolation inside a template string is never closed.
+
+Every `${` has to be closed with a `}` before the template string continues
+or ends. Braces inside the interpolation have to be balanced.",
+        erroneous: "let greeting = `hello ${name",
+        corrected: "let greeting = `hello ${name}`;",
+    },
+    MismatchedRawStr = 15 => Explanation {
+        description: "\
+A raw string is closed with a different number of `#` than it was opened
+with.
+
+A raw string opened with `r#\"` has to be closed with `\"#`. Use more `#`
+on both sides to include `\"#` in the string itself.",
+        erroneous: "let quote = r#\"say \"hi\"\"##;",
+        corrected: "let quote = r#\"say \"hi\"\"#;",
+    },
+    ConfusableIdent = 16 => Explanation {
+        description: "\
+Two different identifiers of a file look the same.
+
+Letters of different scripts can be drawn alike, such as the latin `a` and
+the cyrillic `а`. Identifiers are compared by their confusable skeleton as
+defined by Unicode Technical Standard #39, and a warning is given when two
+of them share a skeleton but are not the same identifier. Identifiers made
+only of ascii characters are not compared with each other.",
+        erroneous: "let cop = 1;\nlet сор = 2;",
+        corrected: "let cop = 1;\nlet officer = 2;",
+    },
+    MixedScriptIdent = 17 => Explanation {
+        description: "\
+An identifier mixes letters of several scripts.
+
+An identifier such as `pаth` spelled with a cyrillic `а` in between latin
+letters reads as a latin word but is a different identifier. Identifiers
+should stay within one script, where han may be combined with hiragana and
+katakana as in japanese, or with hangul as in korean.",
+        erroneous: "let pаth = 1;",
+        corrected: "let path = 1;",
+    },
+    BidiControl = 18 => Explanation {
+        description: "\
+The source contains a unicode bidirectional control character.
+
+Bidirectional controls such as U+202E RIGHT-TO-LEFT OVERRIDE reorder how the
+text around them is displayed. Inside comments and strings they can make
+code look like it does something else than it does, which is known as the
+trojan source attack. They are denied anywhere in the source by default,
+write them as an escape such as `\\u{202E}` in a string where they are
+intended. Tools embedding the lexer can lower the lint to a warning or allow
+it with `with_bidi_level`.",
+        erroneous: "let role = \"user\u{202E}\";",
+        corrected: "let role = \"user\\u{202E}\";",
+    },
+}
+
+impl ErrorCode {
+    pub fn number(&self) -> u32 {
+        *self as u32
+    }
+
+    /// Parses a code as written by `--explain`, e.g. `E0003`
+    pub fn parse(text: &str) -> Option<ErrorCode> {
+        let digits = text.strip_prefix(['E', 'e']).unwrap_or(text);
+        let number = digits.parse::<u32>().ok()?;
+        ErrorCode::ALL
+            .iter()
+            .copied()
+            .find(|code| code.number() == number)
+    }
+
+    /// The explanation as text for a terminal
+    pub fn explain(&self) -> String {
+        let explanation = self.explanation();
+        format!(
+            "{}\n\nErroneous code example:\n\n    {}\n\nCorrected:\n\n    {}\n",
+            explanation.description, explanation.erroneous, explanation.corrected
+        )
+    }
+}
+
+impl fmt::Display for ErrorCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "E{:04}", self.number())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ErrorCode;
+    use crate::tokenize;
+
+    #[test]
+    fn examples_produce_only_their_code() {
+        for &code in ErrorCode::ALL {
+            let explanation = code.explanation();
+            let (_, diagnostics) = tokenize(explanation.erroneous);
+            let codes: Vec<_> = diagnostics
+                .iter()
+                .map(|diagnostic| diagnostic.code)
+                .collect();
+            assert!(!codes.is_empty(), "{}", code);
+            assert!(
+                codes.iter().all(|&found| found == code),
+                "{}: {:?}",
+                code,
+                codes
+            );
+
+            let (_, diagnostics) = tokenize(explanation.corrected);
+            assert!(diagnostics.is_empty(), "{}: {:?}", code, diagnostics);
+        }
+    }
+
+    #[test]
+    fn codes_parse_back() {
+        for &code in ErrorCode::ALL {
+            assert_eq!(ErrorCode::parse(&code.to_string()), Some(code));
+        }
+        assert_eq!(ErrorCode::parse("e3"), Some(ErrorCode::InvalidExponent));
+        assert_eq!(ErrorCode::parse("E9999"), None);
+    }
+}
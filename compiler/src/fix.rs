@@ -0,0 +1,146 @@
+use crate::diagnostic::Suggestion;
+use crate::source_map::byte_offset;
+use crate::tokenize;
+
+// Fixing can uncover more fixes, e.g. a nested block comment needs one `*/`
+// per level, but never this many times in a row
+const MAX_PASSES: usize = 16;
+
+/// Applies every suggestion of the source's diagnostics until none are left
+///
+/// Returns the fixed source and the number of suggestions applied.
+pub fn fix(source: &str) -> (String, usize) {
+    let mut fixed = source.to_string();
+    let mut applied = 0;
+    for _ in 0..MAX_PASSES {
+        let (_, diagnostics) = tokenize(&fixed);
+        let suggestions: Vec<Suggestion> = diagnostics
+            .into_iter()
+            .flat_map(|diagnostic| diagnostic.suggestions)
+            .collect();
+        if suggestions.is_empty() {
+            break;
+        }
+        let (next, count) = apply_suggestions(&fixed, &suggestions);
+        if count == 0 || next == fixed {
+            break;
+        }
+        fixed = next;
+        applied += count;
+    }
+    (fixed, applied)
+}
+
+/// Applies the suggestions to the source they were made for
+///
+/// Insertions at the same offset are applied in the given order, before a
+/// replacement starting there, and repeated suggestions only once. Other
+/// suggestions overlapping an earlier one are skipped, the number applied
+/// is returned along with the new source.
+pub fn apply_suggestions(source: &str, suggestions: &[Suggestion]) -> (String, usize) {
+    let mut unique: Vec<&Suggestion> = Vec::new();
+    for suggestion in suggestions {
+        if !unique.contains(&suggestion) {
+            unique.push(suggestion);
+        }
+    }
+    let mut suggestions = unique;
+    suggestions.sort_by_key(|suggestion| (suggestion.span.low, suggestion.span.high));
+
+    // spans count characters, slicing needs bytes, they are counted on from
+    // the (character, byte) offset of the previous span since the spans
+    // applied are in order
+    let mut counted = (0, 0);
+    let mut byte = |offset: usize| {
+        let (chars, bytes) = counted;
+        let byte = bytes + byte_offset(&source[bytes..], offset - chars);
+        counted = (offset, byte);
+        byte
+    };
+
+    let mut fixed = String::with_capacity(source.len());
+    let mut copied = 0;
+    let mut last_end = 0;
+    let mut applied = 0;
+    for suggestion in suggestions {
+        let span = suggestion.span;
+        if span.low < last_end {
+            continue;
+        }
+        let low = byte(span.low);
+        fixed.push_str(&source[copied..low]);
+        fixed.push_str(&suggestion.replacement);
+        copied = byte(span.high);
+        last_end = span.high;
+        applied += 1;
+    }
+    fixed.push_str(&source[copied..]);
+    (fixed, applied)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{apply_suggestions, fix};
+    use crate::diagnostic::Suggestion;
+    use crate::source_map::FileId;
+    use crate::token::Span;
+
+    fn replace(low: usize, high: usize, text: &str) -> Suggestion {
+        Suggestion::new(Span::set(FileId::default(), low, high), text, "")
+    }
+
+    #[test]
+    fn insertion_goes_before_a_replacement_at_its_offset() {
+        let suggestions = [replace(1, 2, "Y"), replace(1, 1, "X")];
+        assert_eq!(
+            apply_suggestions("abc", &suggestions),
+            ("aXYc".to_string(), 2)
+        );
+        // and after a replacement ending there
+        let suggestions = [replace(1, 1, "X"), replace(0, 1, "Z")];
+        assert_eq!(
+            apply_suggestions("abc", &suggestions),
+            ("ZXbc".to_string(), 2)
+        );
+    }
+
+    #[test]
+    fn insertions_at_one_offset_keep_their_order() {
+        let suggestions = [replace(1, 1, "X"), replace(1, 1, "Y"), replace(1, 1, "X")];
+        assert_eq!(
+            apply_suggestions("ab", &suggestions),
+            ("aXYb".to_string(), 2)
+        );
+    }
+
+    #[test]
+    fn overlapping_suggestions_are_skipped() {
+        let suggestions = [replace(1, 3, "Q"), replace(0, 2, "P")];
+        assert_eq!(
+            apply_suggestions("abcd", &suggestions),
+            ("Pcd".to_string(), 1)
+        );
+        let suggestions = [replace(0, 3, "Q"), replace(1, 1, "X")];
+        assert_eq!(
+            apply_suggestions("abcd", &suggestions),
+            ("Qd".to_string(), 1)
+        );
+    }
+
+    #[test]
+    fn spans_count_characters() {
+        let suggestions = [replace(1, 2, "e"), replace(3, 3, "!")];
+        assert_eq!(
+            apply_suggestions("éé😀", &suggestions),
+            ("ée😀!".to_string(), 2)
+        );
+    }
+
+    #[test]
+    fn fix_applies_until_nothing_is_left() {
+        assert_eq!(fix("let s = 1;"), ("let s = 1;".to_string(), 0));
+        let (fixed, applied) = fix("/* a /* b");
+        assert_eq!(fixed, "/* a /* b*/*/");
+        assert_eq!(applied, 2);
+    }
+}
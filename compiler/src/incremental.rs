@@ -0,0 +1,480 @@
+use std::ops::Range;
+
+use crate::diagnostic::Diagnostic;
+use crate::lexer::{Lexer, Mode, LOOKAHEAD};
+use crate::source_map::{byte_offset, FileId, SourceFile};
+use crate::token::{TemplatePart, Token, TokenKind};
+
+/// Replacement of a range of characters in a source
+#[derive(Debug, Clone, PartialEq)]
+pub struct TextEdit {
+    /// character offsets in the source before the edit
+    pub range: Range<usize>,
+    pub text: String,
+}
+
+impl TextEdit {
+    pub fn new(range: Range<usize>, text: impl Into<String>) -> TextEdit {
+        TextEdit {
+            range,
+            text: text.into(),
+        }
+    }
+
+    /// The source after the edit
+    pub fn apply(&self, source: &str) -> String {
+        let low = byte_offset(source, self.range.start);
+        let high = low + byte_offset(&source[low..], self.range.len());
+        [&source[..low], &self.text, &source[high..]].concat()
+    }
+
+    /// Change in length of the source, in characters
+    pub fn shift(&self) -> isize {
+        self.text.chars().count() as isize - self.range.len() as isize
+    }
+}
+
+/// Tokens lexed again after an edit, to be spliced into the previous ones
+#[derive(Debug)]
+pub struct Relexed<'a> {
+    /// index range of the previous tokens which are replaced
+    pub removed: Range<usize>,
+    /// replacement tokens, which are at removed.start.. after splicing
+    pub tokens: Vec<Token<'a>>,
+    /// diagnostics of the replacement tokens, identifiers are only checked
+    /// for confusables among the replacement tokens
+    pub diagnostics: Vec<Diagnostic>,
+    /// added to the span offsets of the previous tokens after the removed
+    /// ones, which are otherwise unchanged
+    pub shift: isize,
+    // mode stacks before each replacement token and after the last one
+    modes: Vec<Vec<Mode>>,
+    // offset in the previous source where lexing restarted
+    restart: usize,
+}
+
+impl Relexed<'_> {
+    /// Index range of the changed tokens in the spliced token list
+    pub fn changed(&self) -> Range<usize> {
+        self.removed.start..self.removed.start + self.tokens.len()
+    }
+}
+
+/// Mode stacks the lexer had between the tokens of a source
+///
+/// They are kept along with the tokens, so `relex` can restart inside a
+/// template string without tracking the modes from the start of the file.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TokenModes {
+    // (index of a token, mode stack before it) for each token where the
+    // stack changes, the stack before the first token is empty
+    changes: Vec<(usize, Vec<Mode>)>,
+}
+
+impl TokenModes {
+    /// Modes of tokens as returned by `tokenize` for source
+    pub fn new(tokens: &[Token], source: &SourceFile) -> TokenModes {
+        let mut modes = Vec::new();
+        let mut changes = Vec::new();
+        for (index, token) in tokens.iter().enumerate() {
+            let text = || Some(source.slice(token.span.low..token.span.high).to_string());
+            let before = modes.clone();
+            track_modes(&mut modes, &token.kind, token.span.high, text);
+            if modes != before {
+                changes.push((index + 1, modes.clone()));
+            }
+        }
+        TokenModes { changes }
+    }
+
+    /// Updates the modes for the tokens spliced in by relexed
+    pub fn splice(&mut self, relexed: &Relexed) {
+        let removed = &relexed.removed;
+        let kept = self
+            .changes
+            .partition_point(|(index, _)| *index <= removed.start);
+        let after = self
+            .changes
+            .partition_point(|(index, _)| *index <= removed.end);
+        let mut changes = self.changes[..kept].to_vec();
+        let mut last = changes
+            .last()
+            .map_or(Vec::new(), |(_, modes)| modes.clone());
+        let mut push = |index: usize, modes: Vec<Mode>| {
+            if modes != last {
+                last = modes.clone();
+                changes.push((index, modes));
+            }
+        };
+        for (offset, modes) in relexed.modes.iter().enumerate() {
+            push(removed.start + offset, modes.clone());
+        }
+        // interpolations opened after the restart point moved with the edit
+        let moved = removed.start + relexed.tokens.len();
+        for (index, modes) in &self.changes[after..] {
+            let modes = modes
+                .iter()
+                .map(|mode| match *mode {
+                    Mode::Interpolation { start, depth } if start >= relexed.restart => {
+                        Mode::Interpolation {
+                            start: (start as isize + relexed.shift) as usize,
+                            depth,
+                        }
+                    }
+                    ref mode => mode.clone(),
+                })
+                .collect();
+            push(index - removed.end + moved, modes);
+        }
+        self.changes = changes;
+    }
+
+    // mode stack before the token at index
+    fn before(&self, index: usize) -> Vec<Mode> {
+        let changed = self.changes.partition_point(|(at, _)| *at <= index);
+        match changed {
+            0 => Vec::new(),
+            changed => self.changes[changed - 1].1.clone(),
+        }
+    }
+}
+
+/// Lexes only the part of a source affected by an edit
+///
+/// tokens are the previous tokens as returned by `tokenize`, without Eof,
+/// with their modes, and source is the file after the edit. Lexing restarts
+/// at the last token the edit can not affect and stops as soon as it is
+/// back on a previous token boundary in the same state, so an edit inside a
+/// block comment or a string only relexes that token and the ones it
+/// changes. `TokenModes::splice` brings the modes up to date afterwards.
+pub fn relex<'a>(
+    tokens: &[Token],
+    modes: &TokenModes,
+    edit: &TextEdit,
+    source: &'a SourceFile,
+    file: FileId,
+) -> Relexed<'a> {
+    let shift = edit.shift();
+    // interpolations still open at the end of the file are reported there
+    // with spans pointing back at their "${", lexing has to reach the end
+    // again to report them in the new source
+    let reaches_end = tokens
+        .last()
+        .is_some_and(|token| token.kind == TokenKind::UnterminatedInterpolation);
+    let in_order = match reaches_end {
+        true => {
+            let open = tokens
+                .iter()
+                .rev()
+                .take_while(|token| token.kind == TokenKind::UnterminatedInterpolation)
+                .count();
+            &tokens[..tokens.len() - open]
+        }
+        false => tokens,
+    };
+    let start = restart_point(in_order, edit);
+    let mut modes = modes.before(start);
+    let offset = match in_order.get(start) {
+        Some(token) => token.span.low,
+        None => in_order.last().map_or(0, |token| token.span.high),
+    };
+    let byte = source.byte_offset(offset);
+    let mut lexer = Lexer::resume(&source.source, file, offset, byte, modes.clone());
+
+    // end of the inserted text, lexing can not be in sync before it
+    let edit_end = edit.range.start + edit.text.chars().count();
+    let mut new_tokens = Vec::new();
+    // previous tokens are walked along to find where lexing is back in sync,
+    // their modes are tracked in offsets after the edit
+    let mut old = start;
+    let mut modes_known = true;
+    let mut new_modes = Vec::new();
+    loop {
+        new_modes.push(lexer.modes().to_vec());
+        let token = lexer.next_token();
+        if token.kind == TokenKind::Eof {
+            old = tokens.len();
+            break;
+        }
+        let end = token.span.high;
+        new_tokens.push(token);
+        if end < edit_end || reaches_end {
+            continue;
+        }
+        let old_end = (end as isize - shift) as usize;
+        while old < tokens.len() && tokens[old].span.high <= old_end {
+            let token = &tokens[old];
+            let high = (token.span.high as isize + shift) as usize;
+            // the text of tokens touched by the edit is gone
+            let text = || {
+                (token.span.low >= edit.range.end)
+                    .then(|| source.slice(high - token.span.len()..high).to_string())
+            };
+            modes_known &= track_modes(&mut modes, &token.kind, high, text);
+            old += 1;
+        }
+        let on_boundary = old > start && tokens[old - 1].span.high == old_end;
+        if on_boundary && modes_known && modes == lexer.modes() {
+            new_modes.push(modes);
+            break;
+        }
+    }
+    Relexed {
+        removed: start..old,
+        tokens: new_tokens,
+        diagnostics: lexer.take_diagnostics(),
+        shift,
+        modes: new_modes,
+        restart: offset,
+    }
+}
+
+// Index of the first token which has to be lexed again
+fn restart_point(tokens: &[Token], edit: &TextEdit) -> usize {
+    let mut start = tokens.partition_point(|token| token.span.high + LOOKAHEAD <= edit.range.start);
+    // "r" followed by '#' lexes as a raw string once a '"' comes after them
+    let sharp = |index: usize| {
+        tokens
+            .get(index)
+            .is_some_and(|token| token.kind == TokenKind::Sharp)
+    };
+    while start > 0 && (sharp(start - 1) || sharp(start)) {
+        start -= 1;
+    }
+    start
+}
+
+// Updates the mode stack the way lexing a token ending at high did, false
+// if that can not be told
+//
+// Template strings with an invalid escape lose their part, it is recovered
+// from the token's text when it is known.
+fn track_modes(
+    modes: &mut Vec<Mode>,
+    kind: &TokenKind,
+    high: usize,
+    text: impl FnOnce() -> Option<String>,
+) -> bool {
+    let part = match kind {
+        // the '}' before a middle or tail closes the interpolation even
+        // when the template string is unterminated
+        TokenKind::Template { part, .. } => Some(*part),
+        TokenKind::InvalidEscape { .. } => match text() {
+            Some(text) => escaped_template_part(&text),
+            None => return false,
+        },
+        TokenKind::OpenBrace => {
+            if let Some(Mode::Interpolation { depth, .. }) = modes.last_mut() {
+                *depth += 1;
+            }
+            None
+        }
+        TokenKind::CloseBrace => {
+            if let Some(Mode::Interpolation { depth, .. }) = modes.last_mut() {
+                *depth = depth.saturating_sub(1);
+            }
+            None
+        }
+        TokenKind::UnterminatedInterpolation => {
+            modes.pop();
+            None
+        }
+        _ => None,
+    };
+    let interpolation = Mode::Interpolation {
+        start: high.saturating_sub(2),
+        depth: 0,
+    };
+    match part {
+        Some(TemplatePart::Head) => modes.push(interpolation),
+        Some(TemplatePart::Middle) => {
+            modes.pop();
+            modes.push(interpolation);
+        }
+        Some(TemplatePart::Tail) => {
+            modes.pop();
+        }
+        Some(TemplatePart::Full) | None => {}
+    }
+    true
+}
+
+// Part of the template string an InvalidEscape token with the given text
+// is, None when it is a quoted string
+fn escaped_template_part(text: &str) -> Option<TemplatePart> {
+    let after_btick = match text.chars().next() {
+        Some('`') => true,
+        Some('}') => false,
+        _ => return None,
+    };
+    // "${" ends the text unless its '$' is escaped
+    let interpolates = text.strip_suffix("${").is_some_and(|before| {
+        let backslashes = before.len() - before.trim_end_matches('\\').len();
+        backslashes % 2 == 0
+    });
+    Some(match (after_btick, interpolates) {
+        (true, true) => TemplatePart::Head,
+        (true, false) => TemplatePart::Full,
+        (false, true) => TemplatePart::Middle,
+        (false, false) => TemplatePart::Tail,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{relex, TextEdit, TokenModes};
+    use crate::source_map::{FileId, SourceFile};
+    use crate::token::{Span, Token, TokenKind};
+    use crate::tokenize;
+
+    // Splices the relexed tokens and their modes into the previous ones,
+    // which must give the ones of the whole new source
+    fn assert_relexes(source: &str, edit: TextEdit) {
+        let (tokens, _) = tokenize(source);
+        let mut modes = TokenModes::new(&tokens, &SourceFile::new("a.rsc", source));
+        let new_source = edit.apply(source);
+        let new_file = SourceFile::new("a.rsc", new_source.as_str());
+        let relexed = relex(&tokens, &modes, &edit, &new_file, FileId::default());
+        let shifted = |span: Span, shift: isize| {
+            let offset = |offset: usize| (offset as isize + shift) as usize;
+            Span::set(span.file, offset(span.low), offset(span.high))
+        };
+
+        let mut spliced: Vec<(TokenKind, Span)> = Vec::new();
+        for token in &tokens[..relexed.removed.start] {
+            spliced.push((token.kind.clone(), token.span));
+        }
+        for token in &relexed.tokens {
+            spliced.push((token.kind.clone(), token.span));
+        }
+        for token in &tokens[relexed.removed.end..] {
+            spliced.push((token.kind.clone(), shifted(token.span, relexed.shift)));
+        }
+        let (new_tokens, _) = tokenize(&new_source);
+        let expected: Vec<(TokenKind, Span)> = new_tokens
+            .iter()
+            .map(|token| (token.kind.clone(), token.span))
+            .collect();
+        assert_eq!(spliced, expected, "{:?} {:?}", source, edit);
+
+        modes.splice(&relexed);
+        assert_eq!(
+            modes,
+            TokenModes::new(&new_tokens, &new_file),
+            "{:?} {:?}",
+            source,
+            edit
+        );
+    }
+
+    #[test]
+    fn relexed_tokens_splice_into_the_previous_ones() {
+        let seeds = [
+            // inside a block comment, and opening or closing one
+            ("a /* b */ c", TextEdit::new(5..6, "x y")),
+            ("a /* b /* c */ d */ e", TextEdit::new(12..14, "")),
+            ("a /* b */ c", TextEdit::new(2..4, "")),
+            ("a b c", TextEdit::new(2..2, "/*")),
+            // inside a raw string, and changing its hashes
+            ("r#\"a \"b\" c\"# d", TextEdit::new(5..6, "x")),
+            ("r#\"a\"# b", TextEdit::new(1..2, "")),
+            ("r \"a\" b", TextEdit::new(1..1, "#")),
+            ("r#\"a\"# \"b\"", TextEdit::new(6..7, "##")),
+            // inside and around an interpolation
+            ("`a ${b + c} d` e", TextEdit::new(6..7, "{ x }")),
+            ("`a ${b} c ${d} e` f", TextEdit::new(7..8, "")),
+            ("`a ${ `b ${c}` } d`", TextEdit::new(11..12, "`x`")),
+            ("`a ${b} c` d", TextEdit::new(3..5, "")),
+            ("`a ${b", TextEdit::new(6..6, "}`")),
+            ("a ${b}", TextEdit::new(0..0, "`")),
+            // strings and numbers growing into their neighbours
+            ("\"a\" b \"c\"", TextEdit::new(3..3, "\\")),
+            ("1 .. 2", TextEdit::new(1..2, "")),
+            ("a<< =b", TextEdit::new(3..4, "")),
+        ];
+        for (source, edit) in seeds {
+            assert_relexes(source, edit);
+        }
+    }
+
+    #[test]
+    fn random_edits_relex_like_the_whole_source() {
+        const FRAGMENTS: &[&str] = &[
+            "<", "=", ">", ".", "?", "|", "*", "-", "a", "1", " ", "\n", "\"", "`", "${", "}", "{",
+            "/", "r#", "e", "_", "0x", "\\",
+        ];
+        // xorshift, so the sources are the same on every run
+        let mut seed: u64 = 0x1234567;
+        let mut random = |bound: usize| {
+            seed ^= seed << 13;
+            seed ^= seed >> 7;
+            seed ^= seed << 17;
+            (seed % bound as u64) as usize
+        };
+        for _ in 0..5000 {
+            let source: String = (0..random(20))
+                .map(|_| FRAGMENTS[random(FRAGMENTS.len())])
+                .collect();
+            let length = source.chars().count();
+            let start = random(length + 1);
+            let end = start + random(length + 1 - start).min(3);
+            let text: String = (0..random(3))
+                .map(|_| FRAGMENTS[random(FRAGMENTS.len())])
+                .collect();
+            assert_relexes(&source, TextEdit::new(start..end, text));
+        }
+    }
+
+    fn kinds<'a>(tokens: &[Token<'a>]) -> Vec<(TokenKind<'a>, Span)> {
+        tokens
+            .iter()
+            .map(|token| (token.kind.clone(), token.span))
+            .collect()
+    }
+
+    #[test]
+    fn tokens_and_modes_carry_over_edits() {
+        // typing a template string into a file, one edit after the other
+        let mut source = "let a = 1;\nlet é = 2;\n".to_string();
+        let (tokens, _) = tokenize(&source);
+        let mut tokens: Vec<Token> = tokens.into_iter().map(Token::into_owned).collect();
+        let mut modes = TokenModes::default();
+        let edits = [
+            TextEdit::new(8..9, "`é ${"),
+            TextEdit::new(13..13, "{ b }"),
+            TextEdit::new(18..18, "} c`"),
+            TextEdit::new(9..11, ""),
+        ];
+        for edit in edits {
+            source = edit.apply(&source);
+            let file = SourceFile::new("a.rsc", source.as_str());
+            let relexed = relex(&tokens, &modes, &edit, &file, FileId::default());
+            modes.splice(&relexed);
+            let after = tokens.split_off(relexed.removed.end);
+            tokens.truncate(relexed.removed.start);
+            tokens.extend(relexed.tokens.into_iter().map(Token::into_owned));
+            tokens.extend(after.into_iter().map(|mut token| {
+                let offset = |offset: usize| (offset as isize + relexed.shift) as usize;
+                token.span = Span::set(
+                    token.span.file,
+                    offset(token.span.low),
+                    offset(token.span.high),
+                );
+                token
+            }));
+
+            let (expected, _) = tokenize(&source);
+            assert_eq!(kinds(&tokens), kinds(&expected), "{:?}", source);
+            assert_eq!(modes, TokenModes::new(&expected, &file), "{:?}", source);
+        }
+        assert_eq!(source, "let a = `${{ b }} c`;\nlet é = 2;\n");
+    }
+
+    #[test]
+    fn edits_count_characters() {
+        let edit = TextEdit::new(1..3, "ab");
+        assert_eq!(edit.apply("é😀日x"), "éabx");
+        assert_eq!(edit.shift(), 0);
+        assert_eq!(TextEdit::new(4..4, "!").apply("é😀日x"), "é😀日x!");
+    }
+}
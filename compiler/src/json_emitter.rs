@@ -0,0 +1,206 @@
+use std::fmt::Write as _;
+use std::io::Write;
+
+use crate::diagnostic::{Diagnostic, Emitter, Label, Severity};
+use crate::source_map::{Location, SpanLocator};
+use crate::token::Span;
+
+/// Writes every diagnostic as one JSON object on its own line
+///
+/// Lines and columns are one based with columns counted in characters,
+/// byte offsets are zero based and the end offsets are exclusive. The
+/// backtick markup is stripped from messages, labels and notes. Positions
+/// the locator can not resolve are null.
+pub struct JsonEmitter<'a, W: Write> {
+    locator: &'a dyn SpanLocator,
+    out: W,
+}
+
+impl<'a, W: Write> JsonEmitter<'a, W> {
+    pub fn new(locator: &'a dyn SpanLocator, out: W) -> JsonEmitter<'a, W> {
+        JsonEmitter { locator, out }
+    }
+
+    fn to_json(&self, diagnostic: &Diagnostic) -> String {
+        let severity = match diagnostic.severity {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        };
+        let file = self.locator.file_name(diagnostic.primary.span.file);
+        let mut json = String::new();
+        let _ = write!(
+            json,
+            "{{\"code\":{},\"severity\":\"{}\",\"message\":{},\"file\":{},\"span\":{},",
+            diagnostic.code.number(),
+            severity,
+            markup(&diagnostic.message),
+            string(file),
+            self.label(&diagnostic.primary)
+        );
+        let secondary = diagnostic.secondary.iter().map(|label| self.label(label));
+        let notes = diagnostic.notes.iter().map(|note| markup(note));
+        let suggestions = diagnostic.suggestions.iter().map(|suggestion| {
+            format!(
+                "{{{},\"replacement\":{},\"message\":{}}}",
+                self.span(suggestion.span),
+                string(&suggestion.replacement),
+                markup(&suggestion.message)
+            )
+        });
+        let _ = write!(
+            json,
+            "\"secondary\":{},\"notes\":{},\"suggestions\":{}}}",
+            array(secondary),
+            array(notes),
+            array(suggestions)
+        );
+        json
+    }
+
+    fn label(&self, label: &Label) -> String {
+        format!(
+            "{{{},\"label\":{}}}",
+            self.span(label.span),
+            markup(&label.message)
+        )
+    }
+
+    // the fields of a span, without the surrounding braces
+    fn span(&self, span: Span) -> String {
+        let low = self.locator.locate(span.file, span.low);
+        let high = self.locator.locate(span.file, span.high);
+        let field = |location: Option<Location>, value: fn(Location) -> usize| {
+            location.map_or("null".to_string(), |location| value(location).to_string())
+        };
+        format!(
+            "\"byte_start\":{},\"byte_end\":{},\"line_start\":{},\"column_start\":{},\"line_end\":{},\"column_end\":{}",
+            field(low, |location| location.byte_offset),
+            field(high, |location| location.byte_offset),
+            field(low, |location| location.line + 1),
+            field(low, |location| location.column + 1),
+            field(high, |location| location.line + 1),
+            field(high, |location| location.column + 1)
+        )
+    }
+}
+
+impl<W: Write> Emitter for JsonEmitter<'_, W> {
+    fn emit(&mut self, diagnostic: &Diagnostic) {
+        let json = self.to_json(diagnostic);
+        writeln!(self.out, "{}", json).unwrap();
+    }
+}
+
+fn array(items: impl Iterator<Item = String>) -> String {
+    format!("[{}]", items.collect::<Vec<_>>().join(","))
+}
+
+/// Quotes text with backtick markup as a JSON string, dropping the markup
+fn markup(text: &str) -> String {
+    string(&text.replace('`', ""))
+}
+
+/// Quotes and escapes text as a JSON string
+fn string(text: &str) -> String {
+    let mut quoted = String::with_capacity(text.len() + 2);
+    quoted.push('"');
+    for ch in text.chars() {
+        match ch {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            ch if (ch as u32) < 0x20 => {
+                let _ = write!(quoted, "\\u{:04x}", ch as u32);
+            }
+            ch => quoted.push(ch),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{string, JsonEmitter};
+    use crate::diagnostic::{Diagnostic, Emitter, Label, Suggestion};
+    use crate::error_codes::ErrorCode;
+    use crate::source_map::{FileId, Location, SourceMap, SpanLocator};
+    use crate::token::Span;
+
+    fn emit(locator: &dyn SpanLocator, diagnostic: &Diagnostic) -> String {
+        let mut out = Vec::new();
+        JsonEmitter::new(locator, &mut out).emit(diagnostic);
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn strings_are_escaped() {
+        assert_eq!(string("plain é 😀"), "\"plain é 😀\"");
+        assert_eq!(string("\"quoted\" \\"), r#""\"quoted\" \\""#);
+        assert_eq!(string("a\nb\rc\td"), r#""a\nb\rc\td""#);
+        assert_eq!(
+            string("\0\u{1}\u{1f}\u{7f}"),
+            "\"\\u0000\\u0001\\u001f\u{7f}\""
+        );
+        // bidi controls are not control characters in JSON
+        assert_eq!(string("\u{202E}"), "\"\u{202E}\"");
+    }
+
+    #[test]
+    fn diagnostic_is_one_line_of_json() {
+        let mut source_map = SourceMap::new();
+        let file = source_map.add("dir/\"a\".rsc", "let s = \"é\\q\";\n");
+        let diagnostic = Diagnostic::error(
+            ErrorCode::InvalidEscape,
+            "Invalid `Escape`",
+            Label::new(Span::set(file, 10, 12), "This `escape` is invalid"),
+        )
+        .with_note("use `\\\\`")
+        .with_suggestion(Suggestion::new(Span::set(file, 10, 10), "\\", "escape it"));
+
+        assert_eq!(
+            emit(&source_map, &diagnostic),
+            concat!(
+                r#"{"code":12,"severity":"error","message":"Invalid Escape","file":"dir/\"a\".rsc","#,
+                r#""span":{"byte_start":11,"byte_end":13,"line_start":1,"column_start":11,"line_end":1,"column_end":13,"label":"This escape is invalid"},"#,
+                r#""secondary":[],"notes":["use \\\\"],"#,
+                r#""suggestions":[{"byte_start":11,"byte_end":11,"line_start":1,"column_start":11,"line_end":1,"column_end":11,"replacement":"\\","message":"escape it"}]}"#,
+                "\n"
+            )
+        );
+    }
+
+    // Locator which lost the text before offset 5, like a stream lexer
+    struct Dropped;
+
+    impl SpanLocator for Dropped {
+        fn file_name(&self, _: FileId) -> &str {
+            "stream"
+        }
+
+        fn locate(&self, _: FileId, offset: usize) -> Option<Location> {
+            (offset >= 5).then(|| Location {
+                column: offset,
+                byte_offset: offset,
+                ..Location::default()
+            })
+        }
+    }
+
+    #[test]
+    fn unknown_positions_are_null() {
+        let diagnostic = Diagnostic::error(
+            ErrorCode::UnterminatedInterpolation,
+            "Unterminated",
+            Label::new(Span::set(FileId::default(), 3, 5), "here"),
+        );
+        let json = emit(&Dropped, &diagnostic);
+        assert!(
+            json.contains(r#""span":{"byte_start":null,"byte_end":5,"line_start":null,"column_start":null,"line_end":1,"column_end":6,"#),
+            "{}",
+            json
+        );
+    }
+}
@@ -1,19 +1,52 @@
-use crate::error;
+use crate::bigint::BigUint;
+use crate::confusables::IdentLint;
+use crate::diagnostic::{Diagnostic, LintLevel, Severity};
+use crate::source_map::FileId;
 use crate::token;
 
-use error::LErrorHandler;
+use std::borrow::Cow;
+use std::iter::FusedIterator;
 use std::str::Chars;
-use token::{LiteralKind, Span, Token, TokenKind};
+use token::{
+    Base, DocStyle, IntSuffix, LiteralKind, Span, StrForm, Suffix, TemplatePart, Token, TokenKind,
+    OPERATORS,
+};
+use unicode_normalization::{is_nfc, UnicodeNormalization};
 use unicode_properties::UnicodeEmoji;
 
+/// Lexing a token peeks at most this many characters past its end, except
+/// for 'r' which looks past any number of '#' for a raw string
+pub(crate) const LOOKAHEAD: usize = 3;
+
 #[derive(Debug)]
 pub struct Lexer<'a> {
+    source: &'a str,
+    file: FileId,
     input: Chars<'a>,
+    // byte offset in source where the current token starts
+    token_start: usize,
     // length_remaining: usize,
     begin: usize,
     end: usize,
-    error_handler: &'a LErrorHandler<'a>,
+    // offset of the first misplaced '_' in the numeric literal being lexed
+    misplaced_separator: Option<usize>,
+    modes: Vec<Mode>,
+    // sink for the diagnostics of error tokens, drained by take_diagnostics
+    diagnostics: Vec<Diagnostic>,
+    ident_lint: IdentLint,
+    // (offset, character) of the bidi controls in the token being lexed
+    bidi_controls: Vec<(usize, char)>,
+    bidi_level: LintLevel,
+}
+
+/// Lexer state pushed when entering "${" of a template string
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Mode {
+    /// start is the offset of "${", depth counts the '{' opened inside it
+    /// which are not closed yet
+    Interpolation { start: usize, depth: usize },
 }
+
 // for the struct can have the iter().peekable()
 // later check all the pub type access specifier and change accordingly
 // --> checked now for now make it them public later change them to private and
@@ -21,16 +54,78 @@ pub struct Lexer<'a> {
 // --> then give the compiler as the entry pub point
 
 impl<'a> Lexer<'a> {
-    pub fn new(code: &'a str, error_handler: &'a LErrorHandler) -> Lexer<'a> {
+    pub fn new(code: &'a str, file: FileId) -> Lexer<'a> {
         Lexer {
+            source: code,
+            file,
             input: code.chars(),
+            token_start: 0,
             // length_remaining: code.chars().count(), // code.len(),
             begin: 0,
             end: 0,
-            error_handler,
+            misplaced_separator: None,
+            modes: Vec::new(),
+            diagnostics: Vec::new(),
+            ident_lint: IdentLint::default(),
+            bidi_controls: Vec::new(),
+            bidi_level: LintLevel::Deny,
+        }
+    }
+
+    /// Sets how bidi control characters are reported, they are denied by
+    /// default since they can make code read differently from how it runs
+    pub fn with_bidi_level(mut self, level: LintLevel) -> Lexer<'a> {
+        self.bidi_level = level;
+        self
+    }
+
+    /// Lexer continuing at a token boundary, offset is in characters, byte
+    /// is the same offset in bytes and modes is the mode stack the lexer had
+    /// there
+    pub(crate) fn resume(
+        code: &'a str,
+        file: FileId,
+        offset: usize,
+        byte: usize,
+        modes: Vec<Mode>,
+    ) -> Lexer<'a> {
+        Lexer {
+            input: code[byte..].chars(),
+            token_start: byte,
+            begin: offset,
+            end: offset,
+            modes,
+            ..Lexer::new(code, file)
+        }
+    }
+
+    /// Lexer over code which continues a file at character offset, with
+    /// the modes and identifiers the lexer of the text before it had
+    pub(crate) fn continuing(
+        code: &'a str,
+        file: FileId,
+        offset: usize,
+        modes: Vec<Mode>,
+        ident_lint: IdentLint,
+    ) -> Lexer<'a> {
+        Lexer {
+            begin: offset,
+            end: offset,
+            modes,
+            ident_lint,
+            ..Lexer::new(code, file)
         }
     }
 
+    pub(crate) fn modes(&self) -> &[Mode] {
+        &self.modes
+    }
+
+    /// Modes and identifiers seen, to continue lexing with `continuing`
+    pub(crate) fn into_state(self) -> (Vec<Mode>, IdentLint) {
+        (self.modes, self.ident_lint)
+    }
+
     // TODO: maybe add this function in some util
     fn is_whitespace(ch: char) -> bool {
         matches!(
@@ -61,10 +156,25 @@ impl<'a> Lexer<'a> {
 
     pub(crate) fn reset(&mut self) {
         self.begin = self.end;
+        self.token_start = self.byte_position();
+    }
+
+    pub(crate) fn byte_position(&self) -> usize {
+        self.source.len() - self.input.as_str().len()
+    }
+
+    /// Character offset in the file of the next character
+    pub(crate) fn char_position(&self) -> usize {
+        self.end
+    }
+
+    /// Source text from the start of the current token up to here
+    fn token_text(&self) -> &'a str {
+        &self.source[self.token_start..self.byte_position()]
     }
 
     pub(crate) fn create_span(&self) -> Span {
-        Span::set(self.begin, self.end)
+        Span::set(self.file, self.begin, self.end)
     }
 
     // pub(crate) fn unicode_position_span(&self) -> Span {
@@ -87,169 +197,98 @@ impl<'a> Lexer<'a> {
 
     // Not sure if this is needed
     fn read_char(&mut self) {
-        let _ = self.next();
-    }
-
-    fn next(&mut self) -> Option<char> {
-        self.end += 1;
-        self.input.next()
-    }
-
-    fn first(&self) -> char {
-        self.input.clone().next().unwrap_or('\0')
-    }
-
-    fn handle_slash(&mut self) -> TokenKind {
-        match self.first() {
-            '/' => self.line_comment(),
-            '*' => self.block_comment(),
-            '=' => {
-                self.read_char();
-                TokenKind::SlashEq
-            }
-            _ => TokenKind::Slash,
-        }
-    }
-
-    fn handle_colon(&mut self) -> TokenKind {
-        match self.first() {
-            ':' => {
-                self.read_char();
-                TokenKind::Scope
-            }
-            _ => TokenKind::Colon,
-        }
-    }
-
-    fn handle_minus(&mut self) -> TokenKind {
-        match self.first() {
-            '>' => {
-                self.read_char();
-                TokenKind::Arrow
-            }
-            '=' => {
-                self.read_char();
-                TokenKind::MinusEq
-            }
-            _ => TokenKind::Minus,
-        }
+        let _ = self.bump();
     }
 
-    fn handle_equal(&mut self) -> TokenKind {
-        match self.first() {
-            '=' => {
-                self.read_char();
-                TokenKind::EqEq
-            }
-            _ => TokenKind::Eq,
-        }
-    }
-    fn handle_bang(&mut self) -> TokenKind {
-        match self.first() {
-            '=' => {
-                self.read_char();
-                TokenKind::NotEq
-            }
-            _ => TokenKind::Bang,
+    fn bump(&mut self) -> Option<char> {
+        let ch = self.input.next()?;
+        if is_bidi_control(ch) {
+            self.bidi_controls.push((self.end, ch));
         }
+        self.end += 1;
+        Some(ch)
     }
 
-    fn handle_lt(&mut self) -> TokenKind {
-        match self.first() {
-            '=' => {
-                self.read_char();
-                TokenKind::LtEq
-            }
-            _ => TokenKind::Lt,
-        }
+    /// Whether the next characters are "\n" or "\r\n"
+    fn at_line_end(&self) -> bool {
+        self.first() == '\n' || (self.first() == '\r' && self.second() == '\n')
     }
 
-    fn handle_gt(&mut self) -> TokenKind {
-        match self.first() {
-            '=' => {
-                self.read_char();
-                TokenKind::GtEq
-            }
-            _ => TokenKind::Gt,
-        }
+    fn first(&self) -> char {
+        self.input.clone().next().unwrap_or('\0')
     }
 
-    fn handle_ampersand(&mut self) -> TokenKind {
-        match self.first() {
-            '&' => {
-                self.read_char();
-                TokenKind::And
-            }
-            _ => TokenKind::Ampersand,
-        }
+    fn second(&self) -> char {
+        let mut input = self.input.clone();
+        input.next();
+        input.next().unwrap_or('\0')
     }
 
-    fn handle_pipe(&mut self) -> TokenKind {
-        match self.first() {
-            '|' => {
-                self.read_char();
-                TokenKind::Or
-            }
-            _ => TokenKind::Pipe,
-        }
+    fn third(&self) -> char {
+        self.input.clone().nth(2).unwrap_or('\0')
     }
 
-    fn handle_plus(&mut self) -> TokenKind {
-        match self.first() {
-            '=' => {
-                self.read_char();
-                TokenKind::PlusEq
-            }
-            _ => TokenKind::Plus,
+    /// Longest operator in `OPERATORS` starting with first_char
+    fn operator(&mut self, first_char: char) -> Option<TokenKind<'a>> {
+        let rest = self.input.as_str();
+        let (text, kind) = OPERATORS
+            .iter()
+            .filter(|(text, _)| {
+                text.strip_prefix(first_char)
+                    .is_some_and(|tail| rest.starts_with(tail))
+            })
+            .max_by_key(|(text, _)| text.len())?;
+        for _ in 1..text.chars().count() {
+            self.read_char();
         }
+        Some(kind.clone())
     }
 
-    fn handle_asterisk(&mut self) -> TokenKind {
-        match self.first() {
-            '=' => {
+    fn line_comment(&mut self) -> TokenKind<'a> {
+        self.read_char();
+        // "////" is an ordinary comment, just like in rust
+        let style = match (self.first(), self.second()) {
+            ('!', _) => Some(DocStyle::Inner),
+            ('/', c) if c != '/' => Some(DocStyle::Outer),
+            _ => None,
+        };
+        match style {
+            Some(style) => {
                 self.read_char();
-                TokenKind::AsteriskEq
+                let text = self.eat_line();
+                TokenKind::LineDocComment {
+                    style,
+                    text: Cow::Borrowed(text),
+                }
             }
-            _ => TokenKind::Asterisk,
-        }
-    }
-
-    fn handle_caret(&mut self) -> TokenKind {
-        match self.first() {
-            '=' => {
-                self.read_char();
-                TokenKind::CaretEq
+            None => {
+                self.eat_line();
+                TokenKind::LineComment
             }
-            _ => TokenKind::Caret,
         }
     }
 
-    fn handle_percent(&mut self) -> TokenKind {
-        match self.first() {
-            '=' => {
-                self.read_char();
-                TokenKind::PercentEq
-            }
-            _ => TokenKind::Percent,
+    fn block_comment(&mut self) -> TokenKind<'a> {
+        self.bump();
+        // "/**/" and "/***" are ordinary comments, just like in rust
+        let style = match (self.first(), self.second()) {
+            ('!', _) => Some(DocStyle::Inner),
+            ('*', c) if c != '*' && c != '/' => Some(DocStyle::Outer),
+            _ => None,
+        };
+        if style.is_some() {
+            self.read_char();
         }
-    }
-
-    fn line_comment(&mut self) -> TokenKind {
-        self.eat_while(|ch| ch != '\n');
-        TokenKind::LineComment
-    }
-
-    fn block_comment(&mut self) -> TokenKind {
-        self.next();
+        let text_start = self.byte_position();
         let mut depth = 1usize;
-        while let Some(c) = self.next() {
+        while let Some(c) = self.bump() {
             match c {
                 '/' if self.first() == '*' => {
-                    self.next();
+                    self.bump();
                     depth += 1;
                 }
                 '*' if self.first() == '/' => {
-                    self.next();
+                    self.bump();
                     depth -= 1;
                     if depth == 0 {
                         // This block comment is closed, so for a construction like "/* */ */"
@@ -261,8 +300,18 @@ impl<'a> Lexer<'a> {
                 _ => (),
             }
         }
-        TokenKind::BlockComment {
-            terminated: depth == 0,
+        match style {
+            Some(style) => TokenKind::BlockDocComment {
+                style,
+                text: Cow::Borrowed(match depth {
+                    0 => &self.source[text_start..self.byte_position() - 2],
+                    _ => &self.source[text_start..],
+                }),
+                terminated: depth == 0,
+            },
+            None => TokenKind::BlockComment {
+                terminated: depth == 0,
+            },
         }
     }
 
@@ -294,24 +343,25 @@ impl<'a> Lexer<'a> {
     //         .collect::<String>()
     // }
 
-    fn valid_or_invalid_identifier(&mut self, first_char: char) -> TokenKind {
+    fn valid_or_invalid_identifier(&mut self, first_char: char) -> TokenKind<'a> {
         let literal = self.eat_while_get_literal(Self::is_id_continue, Some(first_char));
         // Known prefixes must have been handled earlier. So if
         // we see a prefix here, it is definitely an unknown prefix.
         match self.first() {
             c if !c.is_ascii() && c.is_emoji_char() => self.invalid_ident(),
-            _ => {
-                match Token::literal_to_keyword(&literal) {
-                    Some(keyword) => TokenKind::Keyword { kind: keyword },
-                    None => TokenKind::Ident {
-                        name: literal, // self.get_str_slice_from_chars_direct(self.begin, self.end),
-                    },
-                }
-            }
+            _ => match Token::literal_to_keyword(literal) {
+                Some(keyword) => TokenKind::Keyword { kind: keyword },
+                None if is_nfc(literal) => TokenKind::Ident {
+                    name: Cow::Borrowed(literal),
+                },
+                None => TokenKind::Ident {
+                    name: Cow::Owned(literal.nfc().collect()),
+                },
+            },
         }
     }
 
-    fn invalid_ident(&mut self) -> TokenKind {
+    fn invalid_ident(&mut self) -> TokenKind<'a> {
         // Start is already eaten, eat the rest of identifier.
         self.eat_while(|c| {
             unicode_xid::UnicodeXID::is_xid_continue(c)
@@ -321,35 +371,91 @@ impl<'a> Lexer<'a> {
         TokenKind::InvalidIdent
     }
 
-    fn integer_or_float(&mut self, ch: char) -> TokenKind {
-        let mut literal =
-            self.eat_while_get_literal(|character| matches!(character, '0'..='9'), Some(ch));
+    fn integer_or_float(&mut self, ch: char) -> TokenKind<'a> {
+        self.misplaced_separator = None;
+        match self.integer_or_float_inner(ch) {
+            // the whole literal is eaten before reporting the separator, so
+            // "1__000" stays one token instead of "1" followed by "__000"
+            TokenKind::Literal { .. } if self.misplaced_separator.is_some() => {
+                TokenKind::InvalidSeparator {
+                    offset: self.misplaced_separator.unwrap(),
+                }
+            }
+            kind => kind,
+        }
+    }
+
+    fn integer_or_float_inner(&mut self, ch: char) -> TokenKind<'a> {
+        if ch == '0' {
+            let base = match self.first() {
+                'x' | 'X' => Some(Base::Hexadecimal),
+                'o' | 'O' => Some(Base::Octal),
+                'b' | 'B' => Some(Base::Binary),
+                _ => None,
+            };
+            if let Some(base) = base {
+                self.read_char();
+                return self.prefixed_integer(base);
+            }
+        }
+
+        let literal = self.eat_digits(|character| character.is_ascii_digit(), Some(ch));
         // handle floats here
 
         match self.first() {
+            // "1._5" is a float with a misplaced separator, not a method call
+            '.' if self.second() == '_' && self.third().is_ascii_digit() => {
+                self.read_char();
+                self.handle_float()
+            }
+            // "1..10" is a range and "5.abs()" a method call on an integer
+            '.' if self.second() == '.' || Self::is_id_start(self.second()) => {
+                self.integer(Base::Decimal, literal)
+            }
             '.' => {
-                literal.push(self.next().unwrap());
-                self.handle_float(literal)
+                self.read_char();
+                self.handle_float()
             }
             'e' | 'E' => {
-                literal.push(self.next().unwrap());
-                self.handle_exponent(literal)
+                self.read_char();
+                self.handle_exponent()
             }
-            _ => TokenKind::Literal {
-                kind: LiteralKind::Int {
-                    value: literal.parse::<isize>().unwrap(),
-                },
-            },
+            _ => self.integer(Base::Decimal, literal),
         }
     }
 
-    fn handle_float(&mut self, mut number_prefix: String) -> TokenKind {
+    fn prefixed_integer(&mut self, base: Base) -> TokenKind<'a> {
+        // all decimal digits are eaten even for binary and octal, so that
+        // "0b102" is reported as one literal with an invalid digit
+        let digits = match base {
+            Base::Hexadecimal => self.eat_digits(|ch| ch.is_ascii_hexdigit(), None),
+            _ => self.eat_digits(|ch| ch.is_ascii_digit(), None),
+        };
+
+        if digits.chars().all(|ch| ch == '_') {
+            // atleast one digit must be present after the prefix
+            return TokenKind::EmptyInt { base };
+        }
+
+        if let Some(position) = digits
+            .chars()
+            .position(|ch| ch != '_' && !ch.is_digit(base as u32))
+        {
+            // skip the two prefix characters
+            return TokenKind::InvalidDigit {
+                base,
+                offset: position + 2,
+            };
+        }
+
+        self.integer(base, digits)
+    }
+
+    fn handle_float(&mut self) -> TokenKind<'a> {
         match self.first() {
-            '0'..='9' => {
-                // number_prefix.push(self.next().unwrap());
-                let decimals = self.eat_while_get_literal(|ch| matches!(ch, '0'..='9'), None);
-                number_prefix.push_str(&decimals);
-                self.handle_float_inner(number_prefix)
+            '0'..='9' | '_' => {
+                self.eat_digits(|ch| ch.is_ascii_digit(), None);
+                self.handle_float_inner()
             }
             _ => {
                 // Some Lexical Errors
@@ -359,27 +465,23 @@ impl<'a> Lexer<'a> {
         }
     }
 
-    fn handle_float_inner(&mut self, mut number_prefix: String) -> TokenKind {
+    fn handle_float_inner(&mut self) -> TokenKind<'a> {
         match self.first() {
             'e' | 'E' => {
-                number_prefix.push(self.next().unwrap());
-                self.handle_exponent(number_prefix)
+                self.read_char();
+                self.handle_exponent()
             }
-            _ => TokenKind::Literal {
-                kind: LiteralKind::Float {
-                    value: number_prefix.parse::<f64>().unwrap(),
-                },
-            },
+            _ => self.float(),
         }
     }
 
-    fn handle_exponent(&mut self, mut number_prefix: String) -> TokenKind {
+    fn handle_exponent(&mut self) -> TokenKind<'a> {
         match self.first() {
             '+' | '-' => {
-                number_prefix.push(self.next().unwrap());
-                self.handle_exponent_value(number_prefix)
+                self.read_char();
+                self.handle_exponent_value()
             }
-            '0'..='9' => self.handle_exponent_value(number_prefix),
+            '0'..='9' | '_' => self.handle_exponent_value(),
             _ => {
                 // should return Lexical Error
                 // exponent number must be present after 'e' or 'E'
@@ -388,58 +490,363 @@ impl<'a> Lexer<'a> {
         }
     }
 
-    fn handle_exponent_value(&mut self, mut number_exponent_prefix: String) -> TokenKind {
-        match self.first() {
-            '0'..='9' => {
-                let exponent = self.eat_while_get_literal(|ch| matches!(ch, '0'..='9'), None);
-                number_exponent_prefix.push_str(&exponent);
-                TokenKind::Literal {
-                    kind: LiteralKind::Float {
-                        value: number_exponent_prefix.parse::<f64>().unwrap(),
-                    },
-                }
-            }
-            _ => {
-                // should return Lexical Error
-                // exponent number must be present after 'e' or 'E'
-                TokenKind::InvalidExponent
+    fn handle_exponent_value(&mut self) -> TokenKind<'a> {
+        let exponent = match self.first() {
+            '0'..='9' | '_' => self.eat_digits(|ch| ch.is_ascii_digit(), None),
+            _ => "",
+        };
+        if exponent.chars().all(|ch| ch == '_') {
+            // should return Lexical Error
+            // exponent number must be present after 'e' or 'E'
+            return TokenKind::InvalidExponent;
+        }
+        self.float()
+    }
+
+    fn integer(&mut self, base: Base, digits: &str) -> TokenKind<'a> {
+        let offset = self.end - self.begin;
+        let suffix = match self.literal_suffix() {
+            Ok(suffix) => suffix,
+            Err(offset) => return TokenKind::InvalidSuffix { offset },
+        };
+        let digits = strip_separators(digits);
+        let kind = match suffix {
+            // digits are already checked against the base, so this never fails
+            Some(Suffix::Big) => LiteralKind::BigInt {
+                base,
+                value: BigUint::from_str_radix(&digits, base as u32).unwrap(),
+            },
+            Some(Suffix::Float) if base != Base::Decimal => {
+                return TokenKind::InvalidSuffix { offset };
             }
+            // "12f" is a float written without a decimal point
+            Some(Suffix::Float) => LiteralKind::Float {
+                value: digits.parse::<f64>().unwrap(),
+                suffix,
+            },
+            Some(Suffix::Unsigned) => match u64::from_str_radix(&digits, base as u32) {
+                Ok(value) => LiteralKind::UInt { base, value },
+                Err(_) => return TokenKind::IntOverflow { suffix },
+            },
+            Some(Suffix::Signed) | None => match isize::from_str_radix(&digits, base as u32) {
+                Ok(value) => LiteralKind::Int {
+                    base,
+                    value,
+                    suffix: suffix.map(|_| IntSuffix::Signed),
+                },
+                Err(_) => return TokenKind::IntOverflow { suffix },
+            },
+        };
+        TokenKind::Literal { kind }
+    }
+
+    fn float(&mut self) -> TokenKind<'a> {
+        // float literals always start at the beginning of the token
+        let literal = self.token_text();
+        let offset = self.end - self.begin;
+        let suffix = match self.literal_suffix() {
+            Ok(suffix @ (None | Some(Suffix::Float))) => suffix,
+            Ok(_) => return TokenKind::InvalidSuffix { offset },
+            Err(offset) => return TokenKind::InvalidSuffix { offset },
+        };
+        TokenKind::Literal {
+            kind: LiteralKind::Float {
+                value: strip_separators(literal).parse::<f64>().unwrap(),
+                suffix,
+            },
+        }
+    }
+
+    /// Eats the suffix following a numeric literal, on an unknown suffix
+    /// the offset where it starts is returned as the error
+    fn literal_suffix(&mut self) -> Result<Option<Suffix>, usize> {
+        if !Self::is_id_start(self.first()) {
+            return Ok(None);
+        }
+
+        let offset = self.end - self.begin;
+        let literal = self.eat_while_get_literal(Self::is_id_continue, None);
+        // a '_' is eaten with the digits, so a suffix never starts with one
+        // and "12_u" is a misplaced separator rather than an invalid suffix
+        match Suffix::from_literal(literal) {
+            Some(suffix) => Ok(Some(suffix)),
+            None => Err(offset),
         }
     }
 
-    fn quoted_string(&mut self, ch: char) -> TokenKind {
-        let mut str_literal = String::new();
-        while let Some(c) = self.next() {
+    fn quoted_string(&mut self, ch: char) -> TokenKind<'a> {
+        let content_start = self.byte_position();
+        // stays None until the first escape sequence, since the value is
+        // just the raw text for strings without any
+        let mut str_literal = None;
+        let mut invalid_escape = None;
+        // a quoted string never spans lines, so a missing quote only takes
+        // the rest of its line and lexing goes on with the next one
+        while !self.at_line_end() {
+            let Some(c) = self.bump() else { break };
             match c {
                 character if character == ch => {
+                    if let Some((offset, length)) = invalid_escape {
+                        return TokenKind::InvalidEscape { offset, length };
+                    }
+                    let raw = &self.source[content_start..self.byte_position() - ch.len_utf8()];
                     return TokenKind::Literal {
                         kind: LiteralKind::Str {
                             terminated: true,
                             start: ch,
-                            value: str_literal,
+                            value: str_literal.map_or(Cow::Borrowed(raw), Cow::Owned),
+                            raw: Cow::Borrowed(raw),
+                            form: StrForm::Quoted,
                         },
                     };
                 }
-                '\\' if self.first() == '\\' || self.first() == '"' || self.first() == '\'' => {
-                    // Bump again to skip escaped character.
-                    str_literal.push(c);
-                    str_literal.push(self.first());
-                    self.read_char();
+                '\\' => {
+                    let value = self.cooked_value(&mut str_literal, content_start);
+                    self.push_escape(value, &mut invalid_escape);
+                }
+                _ => {
+                    if let Some(value) = str_literal.as_mut() {
+                        value.push(c);
+                    }
                 }
-                _ => str_literal.push(c),
             }
         }
-        // End of file reached.
+        // End of line or file reached.
+        let raw = &self.source[content_start..self.byte_position()];
         TokenKind::Literal {
             kind: LiteralKind::Str {
                 terminated: false,
                 start: ch,
-                value: str_literal,
+                value: str_literal.map_or(Cow::Borrowed(raw), Cow::Owned),
+                raw: Cow::Borrowed(raw),
+                form: StrForm::Quoted,
+            },
+        }
+    }
+
+    /// Returns the owned value of a string, creating it from the raw text
+    /// before the '\\' which was just eaten if this is the first escape
+    fn cooked_value<'v>(
+        &self,
+        value: &'v mut Option<String>,
+        content_start: usize,
+    ) -> &'v mut String {
+        value
+            .get_or_insert_with(|| self.source[content_start..self.byte_position() - 1].to_string())
+    }
+
+    /// Raw string, the 'r' is already eaten and is followed by zero or
+    /// more '#' and then '"'
+    fn raw_string(&mut self) -> TokenKind<'a> {
+        let hashes = self.eat_while_get_literal(|c| c == '#', None).len();
+        self.read_char();
+        let content_start = self.byte_position();
+        // (offset, hashes) of the closing quote with the most '#' seen so far
+        let mut closest: Option<(usize, usize)> = None;
+        while let Some(c) = self.bump() {
+            if c == '"' {
+                let offset = self.end - self.begin - 1;
+                let found = self
+                    .input
+                    .as_str()
+                    .chars()
+                    .take_while(|&c| c == '#')
+                    .count();
+                if found > hashes {
+                    self.eat_while(|c| c == '#');
+                    return TokenKind::MismatchedRawStr {
+                        expected: hashes,
+                        found,
+                        offset,
+                    };
+                }
+                if found == hashes {
+                    let raw = &self.source[content_start..self.byte_position() - 1];
+                    self.eat_while(|c| c == '#');
+                    return TokenKind::Literal {
+                        kind: LiteralKind::Str {
+                            terminated: true,
+                            start: '"',
+                            value: Cow::Borrowed(raw),
+                            raw: Cow::Borrowed(raw),
+                            form: StrForm::Raw { hashes },
+                        },
+                    };
+                }
+                if closest.is_none_or(|(_, most)| found > most) {
+                    closest = Some((offset, found));
+                }
+            }
+        }
+        // End of file reached, a quote with too few '#' was probably meant
+        // to close the string
+        if let Some((offset, found)) = closest {
+            return TokenKind::MismatchedRawStr {
+                expected: hashes,
+                found,
+                offset,
+            };
+        }
+        let raw = &self.source[content_start..];
+        TokenKind::Literal {
+            kind: LiteralKind::Str {
+                terminated: false,
+                start: '"',
+                value: Cow::Borrowed(raw),
+                raw: Cow::Borrowed(raw),
+                form: StrForm::Raw { hashes },
+            },
+        }
+    }
+
+    /// Multi-line string, the first of the three opening quotes is already
+    /// eaten
+    fn multiline_string(&mut self) -> TokenKind<'a> {
+        self.read_char();
+        self.read_char();
+        let content_start = self.byte_position();
+        while let Some(c) = self.bump() {
+            if c == '"' && self.input.as_str().starts_with("\"\"") {
+                let raw = &self.source[content_start..self.byte_position() - 1];
+                self.read_char();
+                self.read_char();
+                return TokenKind::Literal {
+                    kind: LiteralKind::Str {
+                        terminated: true,
+                        start: '"',
+                        value: Cow::Owned(trim_indent(raw)),
+                        raw: Cow::Borrowed(raw),
+                        form: StrForm::MultiLine,
+                    },
+                };
+            }
+        }
+        // End of file reached.
+        let raw = &self.source[content_start..];
+        TokenKind::Literal {
+            kind: LiteralKind::Str {
+                terminated: false,
+                start: '"',
+                value: Cow::Owned(trim_indent(raw)),
+                raw: Cow::Borrowed(raw),
+                form: StrForm::MultiLine,
             },
         }
     }
 
-    pub fn whitespace(&mut self) -> TokenKind {
+    /// Template string text, either right after the opening '`' or after the
+    /// '}' closing an interpolation. The text ends at '`' or at "${", which
+    /// pushes an interpolation onto the mode stack.
+    fn template_string(&mut self, after_btick: bool) -> TokenKind<'a> {
+        let content_start = self.byte_position();
+        let mut value = None;
+        let mut invalid_escape = None;
+        let (open, close) = match after_btick {
+            true => (TemplatePart::Head, TemplatePart::Full),
+            false => (TemplatePart::Middle, TemplatePart::Tail),
+        };
+        // (part, length of the delimiter which ended it)
+        let (part, delimiter) = loop {
+            match self.bump() {
+                Some('`') => break (Some(close), 1),
+                Some('$') if self.first() == '{' => {
+                    self.read_char();
+                    self.modes.push(Mode::Interpolation {
+                        start: self.end - 2,
+                        depth: 0,
+                    });
+                    break (Some(open), 2);
+                }
+                Some('\\') if matches!(self.first(), '`' | '$') => {
+                    let cooked = self.cooked_value(&mut value, content_start);
+                    cooked.push(self.bump().unwrap());
+                }
+                Some('\\') => {
+                    let cooked = self.cooked_value(&mut value, content_start);
+                    self.push_escape(cooked, &mut invalid_escape);
+                }
+                Some(c) => {
+                    if let Some(value) = value.as_mut() {
+                        value.push(c);
+                    }
+                }
+                // End of file reached.
+                None => break (None, 0),
+            }
+        };
+        if let Some((offset, length)) = invalid_escape {
+            return TokenKind::InvalidEscape { offset, length };
+        }
+        let raw = &self.source[content_start..self.byte_position() - delimiter];
+        TokenKind::Template {
+            terminated: part.is_some(),
+            part: part.unwrap_or(close),
+            value: value.map_or(Cow::Borrowed(raw), Cow::Owned),
+            raw: Cow::Borrowed(raw),
+        }
+    }
+
+    /// Called right after a '\\' was eaten, decodes the escape into value
+    /// or records it as invalid
+    fn push_escape(&mut self, value: &mut String, invalid_escape: &mut Option<(usize, usize)>) {
+        let offset = self.end - self.begin - 1;
+        match self.escape() {
+            Some(character) => value.push(character),
+            None => {
+                let length = self.end - self.begin - offset;
+                invalid_escape.get_or_insert((offset, length));
+            }
+        }
+    }
+
+    /// Decodes the escape sequence after a '\\'
+    fn escape(&mut self) -> Option<char> {
+        // the line break is left for the string to end at
+        if self.at_line_end() {
+            return None;
+        }
+        let ch = self.bump()?;
+        match ch {
+            'n' => Some('\n'),
+            't' => Some('\t'),
+            'r' => Some('\r'),
+            '0' => Some('\0'),
+            '\\' | '"' | '\'' => Some(ch),
+            // "\x7F", exactly two hex digits and ascii only
+            'x' => {
+                let mut value = 0;
+                for _ in 0..2 {
+                    if !self.first().is_ascii_hexdigit() {
+                        return None;
+                    }
+                    value = value * 16 + self.bump().unwrap().to_digit(16).unwrap();
+                }
+                match value {
+                    0..=0x7F => char::from_u32(value),
+                    _ => None,
+                }
+            }
+            // "\u{1F600}", one to six hex digits forming a unicode scalar value
+            'u' => {
+                if self.first() != '{' {
+                    return None;
+                }
+                self.read_char();
+                let digits = self.eat_while_get_literal(|c| c.is_ascii_hexdigit(), None);
+                if self.first() != '}' {
+                    return None;
+                }
+                self.read_char();
+                match digits.len() {
+                    1..=6 => char::from_u32(u32::from_str_radix(digits, 16).unwrap()),
+                    _ => None,
+                }
+            }
+            _ => None,
+        }
+    }
+
+    pub fn whitespace(&mut self) -> TokenKind<'a> {
         self.eat_while(Self::is_whitespace);
         TokenKind::Whitespace
     }
@@ -450,39 +857,125 @@ impl<'a> Lexer<'a> {
         }
     }
 
+    /// Eats the rest of the line and returns it, without the "\n" or "\r\n"
+    /// ending it
+    fn eat_line(&mut self) -> &'a str {
+        let start = self.byte_position();
+        while !self.at_line_end() && !self.is_eof() {
+            self.read_char();
+        }
+        &self.source[start..self.byte_position()]
+    }
+
+    /// Eats while predicate holds and returns the eaten text, including
+    /// first_char when it was eaten just before
     fn eat_while_get_literal(
+        &mut self,
+        predicate: impl FnMut(char) -> bool,
+        first_char: Option<char>,
+    ) -> &'a str {
+        let start = self.byte_position() - first_char.map_or(0, char::len_utf8);
+        self.eat_while(predicate);
+        &self.source[start..self.byte_position()]
+    }
+
+    /// Same as `eat_while_get_literal` but also eats '_' digit separators,
+    /// remembering the first one which is not placed between two digits
+    fn eat_digits(
         &mut self,
         mut predicate: impl FnMut(char) -> bool,
         first_char: Option<char>,
-    ) -> String {
-        let mut literal = String::new();
-        match first_char {
-            Some(value) => literal.push(value),
-            None => {}
+    ) -> &'a str {
+        let start = self.byte_position() - first_char.map_or(0, char::len_utf8);
+        // (character, offset) of the previously eaten character
+        let mut previous = first_char.map(|value| (value, self.end - self.begin - 1));
+        while (predicate(self.first()) || self.first() == '_') && !self.is_eof() {
+            let offset = self.end - self.begin;
+            let character = self.bump().unwrap();
+            // leading or doubled separator
+            if character == '_' && matches!(previous, None | Some(('_', _))) {
+                self.misplaced_separator.get_or_insert(offset);
+            }
+            previous = Some((character, offset));
         }
-        while predicate(self.first()) && !self.is_eof() {
-            literal.push(self.next().unwrap());
-            // self.read_char();
+        // trailing separator, this includes ones next to '.', 'e' or a suffix
+        if let Some(('_', offset)) = previous {
+            self.misplaced_separator.get_or_insert(offset);
+        }
+        &self.source[start..self.byte_position()]
+    }
+
+    /// Reports the diagnostics of a token returned by `lex_token`
+    pub(crate) fn error_report(&mut self, err_token: &Token) {
+        if let Some(diagnostic) = Diagnostic::from_token(err_token) {
+            self.diagnostics.push(diagnostic);
+        }
+        for (offset, ch) in std::mem::take(&mut self.bidi_controls) {
+            let mut diagnostic = Diagnostic::bidi_control(err_token, offset, ch);
+            match self.bidi_level {
+                LintLevel::Allow => continue,
+                LintLevel::Warn => diagnostic.severity = Severity::Warning,
+                LintLevel::Deny => {}
+            }
+            self.diagnostics.push(diagnostic);
         }
-        literal
+        if let TokenKind::Ident { name } = &err_token.kind {
+            let warnings = self.ident_lint.check(name, err_token.span);
+            self.diagnostics.extend(warnings);
+        }
+    }
+
+    /// Diagnostics of the error tokens returned since the last call
+    pub fn take_diagnostics(&mut self) -> Vec<Diagnostic> {
+        std::mem::take(&mut self.diagnostics)
     }
 
-    fn error_report(&self, err_token: &Token) {
-        self.error_handler.error_report(err_token);
+    pub fn next_token(&mut self) -> Token<'a> {
+        let token = self.lex_token();
+        self.error_report(&token);
+        token
     }
 
-    pub fn next_token(&mut self) -> Token {
+    /// Lexes the next token without reporting its diagnostics yet, so a
+    /// token cut short by the end of the input can be dropped
+    pub(crate) fn lex_token(&mut self) -> Token<'a> {
         // first character of the token
-        let first_char = match self.next() {
+        let first_char = match self.bump() {
             Some(character) => character,
-            None => return Token::new(TokenKind::Eof, Span::set(0, 0)), //self.unicode_position_span()),
+            None => match self.modes.pop() {
+                // "${" never closed, report it before the end of input
+                Some(Mode::Interpolation { start, .. }) => {
+                    return Token::new(
+                        TokenKind::UnterminatedInterpolation,
+                        Span::set(self.file, start, start + 2),
+                    );
+                }
+                // empty span at the end of the file
+                None => return Token::new(TokenKind::Eof, self.create_span()),
+            },
         };
         let token_kind = match first_char {
-            // slash or comment or block comment
-            '/' => self.handle_slash(),
+            // "#!" starting the file, so scripts can be run from a shell
+            '#' if self.begin == 0 && self.first() == '!' => {
+                self.eat_line();
+                TokenKind::Shebang
+            }
 
-            // whitespace sequence
-            ch if Self::is_whitespace(ch) => self.whitespace(),
+            // line comment or block comment
+            '/' if self.first() == '/' => self.line_comment(),
+            '/' if self.first() == '*' => self.block_comment(),
+
+            // whitespace sequence, bidi controls outside of comments and
+            // strings are as invisible as whitespace and reported as well
+            ch if Self::is_whitespace(ch) || is_bidi_control(ch) => self.whitespace(),
+
+            // raw string, 'r' directly followed by '"' or '#'
+            'r' if self.input.as_str().trim_start_matches('#').starts_with('"') => {
+                self.raw_string()
+            }
+
+            // multi-line string
+            '"' if self.input.as_str().starts_with("\"\"") => self.multiline_string(),
 
             // identifier and keyword
             ch if Self::is_id_start(ch) => self.valid_or_invalid_identifier(first_char),
@@ -494,35 +987,28 @@ impl<'a> Lexer<'a> {
 
             ch if matches!(ch, '\'' | '\"') => self.quoted_string(ch),
 
-            // One-symbol tokens.
-            ';' => TokenKind::Semi,
-            ',' => TokenKind::Comma,
-            '.' => TokenKind::Dot,
-            '(' => TokenKind::OpenPara,
-            ')' => TokenKind::ClosePara,
-            '{' => TokenKind::OpenBrace,
-            '}' => TokenKind::CloseBrace,
-            '[' => TokenKind::OpenBracket,
-            ']' => TokenKind::CloseBracket,
-            '@' => TokenKind::At,
-            '#' => TokenKind::Sharp,
-            '~' => TokenKind::Tilde,
-            '?' => TokenKind::Question,
-            ':' => self.handle_colon(),
-            '$' => TokenKind::Dollar,
-            '=' => self.handle_equal(),
-            '!' => self.handle_bang(),
-            '<' => self.handle_lt(),
-            '>' => self.handle_gt(),
-            '-' => self.handle_minus(),
-            '&' => self.handle_ampersand(),
-            '|' => self.handle_pipe(),
-            '+' => self.handle_plus(),
-            '*' => self.handle_asterisk(),
-            '^' => self.handle_caret(),
-            '%' => self.handle_percent(),
-
-            _ => TokenKind::Unknown,
+            '{' => {
+                if let Some(Mode::Interpolation { depth, .. }) = self.modes.last_mut() {
+                    *depth += 1;
+                }
+                TokenKind::OpenBrace
+            }
+            '}' => match self.modes.last_mut() {
+                // closes the "${", so the template string continues
+                Some(Mode::Interpolation { depth: 0, .. }) => {
+                    self.modes.pop();
+                    self.template_string(false)
+                }
+                Some(Mode::Interpolation { depth, .. }) => {
+                    *depth -= 1;
+                    TokenKind::CloseBrace
+                }
+                None => TokenKind::CloseBrace,
+            },
+            '`' => self.template_string(true),
+
+            // operators and punctuation
+            ch => self.operator(ch).unwrap_or(TokenKind::Unknown),
         };
 
         //let curr_length = self.input.clone().count();
@@ -532,7 +1018,661 @@ impl<'a> Lexer<'a> {
         let res = Token::new(token_kind, self.create_span());
         //self.reset_unicode_position();
         self.reset();
-        self.error_report(&res);
         res
     }
 }
+
+impl<'a> Iterator for Lexer<'a> {
+    type Item = Token<'a>;
+
+    /// Same as `next_token` but the iteration ends at Eof instead of
+    /// yielding it, whitespace and comments are still yielded
+    fn next(&mut self) -> Option<Token<'a>> {
+        let token = self.next_token();
+        match token.kind {
+            TokenKind::Eof => None,
+            _ => Some(token),
+        }
+    }
+}
+
+// next_token keeps returning Eof once the input is exhausted
+impl FusedIterator for Lexer<'_> {}
+
+/// Unicode controls changing the display order of text, which can make
+/// code read differently from how it lexes
+pub(crate) fn is_bidi_control(ch: char) -> bool {
+    matches!(
+        ch,
+        '\u{061C}' // ARABIC LETTER MARK
+        | '\u{200E}' // LEFT-TO-RIGHT MARK
+        | '\u{200F}' // RIGHT-TO-LEFT MARK
+        | '\u{202A}'..='\u{202E}' // embeddings, overrides and their pop
+        | '\u{2066}'..='\u{2069}' // isolates and their pop
+    )
+}
+
+/// Removes the '_' digit separators, borrowing when there are none
+fn strip_separators(digits: &str) -> Cow<'_, str> {
+    match digits.contains('_') {
+        true => Cow::Owned(digits.replace('_', "")),
+        false => Cow::Borrowed(digits),
+    }
+}
+
+/// Removes the first and the last line when they are blank, then the
+/// indentation common to all non-blank lines, counted in characters
+///
+/// Lines end with '\n' in the result even when they end with "\r\n" in
+/// the source.
+fn trim_indent(text: &str) -> String {
+    let mut lines: Vec<&str> = text
+        .split('\n')
+        .map(|line| line.strip_suffix('\r').unwrap_or(line))
+        .collect();
+    if lines.first().is_some_and(|line| line.trim().is_empty()) {
+        lines.remove(0);
+    }
+    if lines.last().is_some_and(|line| line.trim().is_empty()) {
+        lines.pop();
+    }
+    let indent = lines
+        .iter()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| line.chars().take_while(|ch| ch.is_whitespace()).count())
+        .min()
+        .unwrap_or(0);
+    lines
+        .iter()
+        .map(|line| match line.trim().is_empty() {
+            true => "",
+            false => match line.char_indices().nth(indent) {
+                Some((start, _)) => &line[start..],
+                None => "",
+            },
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Lexer;
+    use crate::diagnostic::{Diagnostic, LintLevel, Severity};
+    use crate::error_codes::ErrorCode;
+    use crate::source_map::FileId;
+    use crate::token::{
+        Base, DocStyle, IntSuffix, LiteralKind, Span, Suffix, TemplatePart, TokenKind, OPERATORS,
+    };
+    use crate::tokenize;
+
+    fn kinds(source: &str) -> Vec<TokenKind<'_>> {
+        let (tokens, diagnostics) = tokenize(source);
+        assert!(diagnostics.is_empty(), "{:?}: {:?}", source, diagnostics);
+        tokens.into_iter().map(|token| token.kind).collect()
+    }
+
+    fn int(value: isize) -> TokenKind<'static> {
+        TokenKind::Literal {
+            kind: LiteralKind::Int {
+                base: Base::Decimal,
+                value,
+                suffix: None,
+            },
+        }
+    }
+
+    fn ident(name: &str) -> TokenKind<'_> {
+        TokenKind::Ident { name: name.into() }
+    }
+
+    #[test]
+    fn every_operator_lexes_to_itself() {
+        for (text, kind) in OPERATORS {
+            assert_eq!(kinds(text), std::slice::from_ref(kind), "{:?}", text);
+        }
+    }
+
+    #[test]
+    fn operators_take_the_longest_match() {
+        assert_eq!(kinds("a<<=b"), [ident("a"), TokenKind::ShlEq, ident("b")]);
+        assert_eq!(kinds("1...2"), [int(1), TokenKind::Ellipsis, int(2)]);
+        assert_eq!(kinds("?.5"), [TokenKind::OptionalDot, int(5)]);
+        assert_eq!(
+            kinds("a-->b"),
+            [ident("a"), TokenKind::Decrement, TokenKind::Gt, ident("b")]
+        );
+        assert_eq!(
+            kinds("a|||b"),
+            [ident("a"), TokenKind::Or, TokenKind::Pipe, ident("b")]
+        );
+        assert_eq!(
+            kinds("a>>==b"),
+            [ident("a"), TokenKind::ShrEq, TokenKind::Eq, ident("b")]
+        );
+    }
+
+    #[test]
+    fn stray_backslash_is_unknown() {
+        let (tokens, diagnostics) = tokenize("1 \\ 2");
+        assert_eq!(tokens[2].kind, TokenKind::Unknown);
+        assert_eq!(tokens[2].span, Span::set(FileId::default(), 2, 3));
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].code, ErrorCode::UnknownToken);
+    }
+
+    #[test]
+    fn unsigned_literals_take_the_u64_range() {
+        let uint = |base, value| TokenKind::Literal {
+            kind: LiteralKind::UInt { base, value },
+        };
+        assert_eq!(
+            kinds("18446744073709551615u"),
+            [uint(Base::Decimal, u64::MAX)]
+        );
+        assert_eq!(
+            kinds("0xFFFF_FFFF_FFFF_FFFFu"),
+            [uint(Base::Hexadecimal, u64::MAX)]
+        );
+
+        let (_, diagnostics) = tokenize("18446744073709551616u");
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].code, ErrorCode::IntOverflow);
+        assert!(diagnostics[0].notes[0].contains(&u64::MAX.to_string()));
+        let (_, diagnostics) = tokenize("9223372036854775808");
+        assert!(diagnostics[0].notes[0].contains(&isize::MAX.to_string()));
+    }
+
+    fn template<'a>(part: TemplatePart, value: &'a str, raw: &'a str) -> TokenKind<'a> {
+        TokenKind::Template {
+            part,
+            terminated: true,
+            value: value.into(),
+            raw: raw.into(),
+        }
+    }
+
+    // Value of the only token of a source, which must be a string
+    fn string_value(source: &str) -> String {
+        match &kinds(source)[..] {
+            [TokenKind::Literal {
+                kind: LiteralKind::Str { value, .. },
+            }] => value.to_string(),
+            kinds => panic!("{:?}: {:?}", source, kinds),
+        }
+    }
+
+    #[test]
+    fn escapes_are_decoded() {
+        let escapes = [
+            (r#""\n\t\r\0""#, "\n\t\r\0"),
+            (r#""\\ \" \'""#, "\\ \" '"),
+            (r#"'\' \"'"#, "' \""),
+            (r#""\x41\x7F\x7f""#, "A\x7F\x7F"),
+            (r#""\u{0}\u{e9}\u{1F600}\u{10FFFF}""#, "\0é😀\u{10FFFF}"),
+            (r#""a\u{00_41}b""#, "a"),
+        ];
+        for (source, value) in &escapes[..5] {
+            assert_eq!(string_value(source), *value, "{}", source);
+        }
+        // separators are not digits, so the escape ends at the '_'
+        let (_, diagnostics) = tokenize(escapes[5].0);
+        assert_eq!(diagnostics[0].code, ErrorCode::InvalidEscape);
+
+        assert_eq!(
+            kinds(r"`\` \$ \n`"),
+            [template(TemplatePart::Full, "` $ \n", r"\` \$ \n")]
+        );
+    }
+
+    #[test]
+    fn invalid_escapes_report_their_offset_and_length() {
+        // (source, offset of the '\' from the start of the last token, length)
+        let escapes = [
+            (r#""\x80""#, 1, 4),
+            (r#""\xFF""#, 1, 4),
+            (r#""\xG1""#, 1, 2),
+            (r#""\x4""#, 1, 3),
+            (r#""\u{}""#, 1, 4),
+            (r#""\u{D800}""#, 1, 8),
+            (r#""\u{DFFF}""#, 1, 8),
+            (r#""\u{110000}""#, 1, 10),
+            (r#""\u{1234567}""#, 1, 11),
+            (r#""\u{12""#, 1, 5),
+            (r#""\u12""#, 1, 2),
+            (r#""ab\q""#, 3, 2),
+            (r#"'é\q'"#, 2, 2),
+            // only the first invalid escape is reported
+            (r#""\q\x80""#, 1, 2),
+            ("`a${b}c\\q`", 2, 2),
+            // a line break can not be escaped, the template string goes on
+            // on the next line
+            ("`a\\\nb`", 2, 1),
+        ];
+        for (source, offset, length) in escapes {
+            let (tokens, diagnostics) = tokenize(source);
+            let token = tokens.last().unwrap();
+            assert_eq!(
+                token.kind,
+                TokenKind::InvalidEscape { offset, length },
+                "{}",
+                source
+            );
+            assert_eq!(diagnostics.len(), 1, "{}: {:?}", source, diagnostics);
+            assert_eq!(diagnostics[0].code, ErrorCode::InvalidEscape);
+            let low = token.span.low + offset;
+            assert_eq!(
+                diagnostics[0].primary.span,
+                Span::set(token.span.file, low, low + length),
+                "{}",
+                source
+            );
+        }
+
+        // a quoted string ends at the line break instead
+        let (tokens, diagnostics) = tokenize("\"a\\\nb");
+        assert_eq!(tokens.len(), 3);
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].code, ErrorCode::UnterminatedStr);
+    }
+
+    #[test]
+    fn braces_inside_an_interpolation_do_not_close_it() {
+        assert_eq!(
+            kinds("`a${ {b} }c`"),
+            [
+                template(TemplatePart::Head, "a", "a"),
+                TokenKind::Whitespace,
+                TokenKind::OpenBrace,
+                ident("b"),
+                TokenKind::CloseBrace,
+                TokenKind::Whitespace,
+                template(TemplatePart::Tail, "c", "c"),
+            ]
+        );
+        assert_eq!(
+            kinds("`${{{}}}${1}`"),
+            [
+                template(TemplatePart::Head, "", ""),
+                TokenKind::OpenBrace,
+                TokenKind::OpenBrace,
+                TokenKind::CloseBrace,
+                TokenKind::CloseBrace,
+                template(TemplatePart::Middle, "", ""),
+                int(1),
+                template(TemplatePart::Tail, "", ""),
+            ]
+        );
+    }
+
+    #[test]
+    fn templates_nest_inside_interpolations() {
+        assert_eq!(
+            kinds("`a${`b${c}d`}e`"),
+            [
+                template(TemplatePart::Head, "a", "a"),
+                template(TemplatePart::Head, "b", "b"),
+                ident("c"),
+                template(TemplatePart::Tail, "d", "d"),
+                template(TemplatePart::Tail, "e", "e"),
+            ]
+        );
+        assert_eq!(
+            kinds("`${`${`x`}`}`"),
+            [
+                template(TemplatePart::Head, "", ""),
+                template(TemplatePart::Head, "", ""),
+                template(TemplatePart::Full, "x", "x"),
+                template(TemplatePart::Tail, "", ""),
+                template(TemplatePart::Tail, "", ""),
+            ]
+        );
+    }
+
+    #[test]
+    fn escaped_dollar_does_not_interpolate() {
+        assert_eq!(
+            kinds(r"`a\${b}`"),
+            [template(TemplatePart::Full, "a${b}", r"a\${b}")]
+        );
+        // an escaped backslash leaves the "${" to interpolate
+        assert_eq!(
+            kinds(r"`\\${b}`"),
+            [
+                template(TemplatePart::Head, "\\", r"\\"),
+                ident("b"),
+                template(TemplatePart::Tail, "", ""),
+            ]
+        );
+    }
+
+    #[test]
+    fn unclosed_interpolation_is_reported_at_its_start() {
+        let (tokens, diagnostics) = tokenize("let s = `a ${b + `c ${d");
+        let open: Vec<_> = tokens
+            .iter()
+            .filter(|token| token.kind == TokenKind::UnterminatedInterpolation)
+            .map(|token| token.span)
+            .collect();
+        // the innermost interpolation is closed first
+        assert_eq!(
+            open,
+            [
+                Span::set(FileId::default(), 20, 22),
+                Span::set(FileId::default(), 11, 13)
+            ]
+        );
+        let reported: Vec<_> = diagnostics
+            .iter()
+            .map(|diagnostic| (diagnostic.code, diagnostic.primary.span))
+            .collect();
+        assert_eq!(
+            reported,
+            [
+                (ErrorCode::UnterminatedInterpolation, open[0]),
+                (ErrorCode::UnterminatedInterpolation, open[1]),
+            ]
+        );
+    }
+
+    #[test]
+    fn multi_line_strings_trim_their_indent() {
+        let strings = [
+            ("\"\"\"\n    a\n      b\n\n    c\n    \"\"\"", "a\n  b\n\nc"),
+            ("\"\"\"\r\n    a\r\n      b\r\n    \"\"\"", "a\n  b"),
+            ("\"\"\"a\r\n b\"\"\"", "a\n b"),
+            // indentation is counted in characters, whatever their width
+            ("\"\"\"\n\t\ta\n\t\t  b\n\"\"\"", "a\n  b"),
+            ("\"\"\"\n\u{3000}a\n  b\n\"\"\"", "a\n b"),
+            (
+                "\"\"\"\n\u{3000}\u{3000}é\n\u{3000}\u{3000}\u{3000}b\n\"\"\"",
+                "é\n\u{3000}b",
+            ),
+        ];
+        for (source, value) in strings {
+            assert_eq!(string_value(source), value, "{:?}", source);
+        }
+    }
+
+    #[test]
+    fn raw_strings_close_at_their_own_hashes() {
+        assert_eq!(string_value(r##"r#"a"b"#"##), "a\"b");
+        assert_eq!(string_value(r#"r"a\n""#), r"a\n");
+
+        let mismatched = [
+            // too many '#', the string ends there
+            (r###"r#"x"##"###, 1, 2, 4),
+            // never closed, the quote with the most '#' was probably meant
+            (r###"r##"x"#"###, 2, 1, 5),
+            (r###"r###"a"# b"## c"#"###, 3, 2, 10),
+            (r###"r##"a"# b"#"###, 2, 1, 5),
+        ];
+        for (source, expected, found, offset) in mismatched {
+            let (tokens, diagnostics) = tokenize(source);
+            assert_eq!(
+                tokens[0].kind,
+                TokenKind::MismatchedRawStr {
+                    expected,
+                    found,
+                    offset,
+                },
+                "{}",
+                source
+            );
+            assert_eq!(tokens.len(), 1, "{}", source);
+            assert_eq!(diagnostics.len(), 1);
+            assert_eq!(diagnostics[0].code, ErrorCode::MismatchedRawStr);
+        }
+    }
+
+    #[test]
+    fn quoted_strings_end_at_their_line() {
+        let (tokens, diagnostics) = tokenize("let a = \"one\nlet b = 'two\r\nlet c = \"three\";");
+        let strings: Vec<_> = tokens
+            .iter()
+            .filter_map(|token| match &token.kind {
+                TokenKind::Literal {
+                    kind:
+                        LiteralKind::Str {
+                            terminated, value, ..
+                        },
+                } => Some((*terminated, value.as_ref(), token.span.low, token.span.high)),
+                _ => None,
+            })
+            .collect();
+        // the line break is not part of the string
+        assert_eq!(
+            strings,
+            [
+                (false, "one", 8, 12),
+                (false, "two", 21, 25),
+                (true, "three", 35, 42)
+            ]
+        );
+        let codes: Vec<_> = diagnostics
+            .iter()
+            .map(|diagnostic| diagnostic.code)
+            .collect();
+        assert_eq!(
+            codes,
+            [ErrorCode::UnterminatedStr, ErrorCode::UnterminatedStr]
+        );
+
+        // a closing quote on a later line starts another string there
+        let (tokens, diagnostics) = tokenize("\"a\nb\"");
+        assert_eq!(tokens.len(), 4);
+        assert_eq!(tokens[2].kind, ident("b"));
+        assert_eq!(diagnostics.len(), 2);
+    }
+
+    #[test]
+    fn dot_after_an_integer() {
+        assert_eq!(kinds("1..10"), [int(1), TokenKind::DotDot, int(10)]);
+        assert_eq!(kinds("1..=2"), [int(1), TokenKind::DotDotEq, int(2)]);
+        assert_eq!(
+            kinds("5.abs()"),
+            [
+                int(5),
+                TokenKind::Dot,
+                ident("abs"),
+                TokenKind::OpenPara,
+                TokenKind::ClosePara,
+            ]
+        );
+        // an exponent needs digits before it, so this is a method call too
+        assert_eq!(kinds("1.e5"), [int(1), TokenKind::Dot, ident("e5")]);
+        assert_eq!(kinds("1._x"), [int(1), TokenKind::Dot, ident("_x")]);
+
+        let (tokens, diagnostics) = tokenize("1.");
+        assert_eq!(tokens[0].kind, TokenKind::InvalidDecimal);
+        assert_eq!(diagnostics[0].code, ErrorCode::InvalidDecimal);
+    }
+
+    #[test]
+    fn separator_after_a_decimal_point_is_misplaced() {
+        let (tokens, diagnostics) = tokenize("1._5");
+        assert_eq!(tokens.len(), 1);
+        assert_eq!(tokens[0].kind, TokenKind::InvalidSeparator { offset: 2 });
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].code, ErrorCode::InvalidSeparator);
+    }
+
+    #[test]
+    fn suffixes_pick_the_literal_kind() {
+        let float = |value| TokenKind::Literal {
+            kind: LiteralKind::Float {
+                value,
+                suffix: Some(Suffix::Float),
+            },
+        };
+        assert_eq!(kinds("12f"), [float(12.0)]);
+        assert_eq!(kinds("1_000f"), [float(1000.0)]);
+        // too large for an int, but not for a float
+        assert_eq!(kinds("99999999999999999999f"), [float(1e20)]);
+        assert_eq!(
+            kinds("12i"),
+            [TokenKind::Literal {
+                kind: LiteralKind::Int {
+                    base: Base::Decimal,
+                    value: 12,
+                    suffix: Some(IntSuffix::Signed),
+                },
+            }]
+        );
+        // 'f' is a hexadecimal digit
+        assert_eq!(
+            kinds("0x1f"),
+            [TokenKind::Literal {
+                kind: LiteralKind::Int {
+                    base: Base::Hexadecimal,
+                    value: 31,
+                    suffix: None,
+                },
+            }]
+        );
+
+        let (tokens, _) = tokenize("0b1f");
+        assert_eq!(tokens[0].kind, TokenKind::InvalidSuffix { offset: 3 });
+        let (_, diagnostics) = tokenize("99999999999999999999i");
+        assert_eq!(diagnostics[0].code, ErrorCode::IntOverflow);
+        assert!(diagnostics[0].primary.message.ends_with("an int"));
+    }
+
+    fn bidi_diagnostics(source: &str, level: LintLevel) -> Vec<Diagnostic> {
+        let mut lexer = Lexer::new(source, FileId::default()).with_bidi_level(level);
+        lexer.by_ref().for_each(drop);
+        lexer.take_diagnostics()
+    }
+
+    #[test]
+    fn bidi_controls_are_reported_where_they_are() {
+        // (source, place in the label, replacement suggested)
+        let cases = [
+            ("let a\u{202E} = 1;", "`code`", Some(" ")),
+            ("// a\u{202E}b", "a `comment`", Some("")),
+            ("/* a\u{202E}b */", "a `comment`", Some("")),
+            ("/// a\u{202E}b\nlet a = 1;", "a `comment`", Some("")),
+            ("let a = \"a\u{202E}b\";", "a `string`", Some("\\u{202E}")),
+            ("let a = `a\u{202E}${1}`;", "a `string`", Some("\\u{202E}")),
+            ("let a = r#\"a\u{202E}b\"#;", "a `raw string`", None),
+            (
+                "let a = \"\"\"\n  a\u{202E}b\n\"\"\";",
+                "a `multi-line string`",
+                None,
+            ),
+        ];
+        for (source, place, replacement) in cases {
+            let diagnostics = bidi_diagnostics(source, LintLevel::Deny);
+            assert_eq!(diagnostics.len(), 1, "{}", source);
+            let diagnostic = &diagnostics[0];
+            assert_eq!(diagnostic.code, ErrorCode::BidiControl);
+            assert_eq!(diagnostic.severity, Severity::Error);
+            let offset = source.chars().position(|ch| ch == '\u{202E}').unwrap();
+            assert_eq!(
+                diagnostic.primary.span,
+                Span::set(FileId::default(), offset, offset + 1)
+            );
+            assert_eq!(
+                diagnostic.primary.message,
+                format!("`U+202E` RIGHT-TO-LEFT OVERRIDE in {}", place)
+            );
+            let suggested: Vec<_> = diagnostic
+                .suggestions
+                .iter()
+                .map(|suggestion| suggestion.replacement.as_str())
+                .collect();
+            assert_eq!(suggested, Vec::from_iter(replacement), "{}", source);
+        }
+    }
+
+    #[test]
+    fn bidi_controls_follow_their_lint_level() {
+        let source = "let a = \"\u{2067}b\u{2069}\";";
+        let severities = |level| {
+            bidi_diagnostics(source, level)
+                .iter()
+                .map(|diagnostic| diagnostic.severity)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(severities(LintLevel::Deny), [Severity::Error; 2]);
+        assert_eq!(severities(LintLevel::Warn), [Severity::Warning; 2]);
+        assert_eq!(severities(LintLevel::Allow), []);
+        // denied unless told otherwise
+        let (_, diagnostics) = tokenize(source);
+        assert_eq!(diagnostics.len(), 2);
+        // other diagnostics are not affected
+        let diagnostics = bidi_diagnostics("\"\u{202E}", LintLevel::Allow);
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].code, ErrorCode::UnterminatedStr);
+    }
+
+    fn line_doc(style: DocStyle, text: &str) -> TokenKind<'_> {
+        TokenKind::LineDocComment {
+            style,
+            text: text.into(),
+        }
+    }
+
+    fn block_doc(style: DocStyle, text: &str) -> TokenKind<'_> {
+        TokenKind::BlockDocComment {
+            style,
+            text: text.into(),
+            terminated: true,
+        }
+    }
+
+    #[test]
+    fn doc_comments() {
+        assert_eq!(
+            kinds("/// outer\n"),
+            [line_doc(DocStyle::Outer, " outer"), TokenKind::Whitespace]
+        );
+        assert_eq!(kinds("//! inner"), [line_doc(DocStyle::Inner, " inner")]);
+        assert_eq!(
+            kinds("/** outer */"),
+            [block_doc(DocStyle::Outer, " outer ")]
+        );
+        assert_eq!(
+            kinds("/*! inner */"),
+            [block_doc(DocStyle::Inner, " inner ")]
+        );
+        assert_eq!(kinds("//!"), [line_doc(DocStyle::Inner, "")]);
+        // more or fewer stars or slashes make ordinary comments
+        assert_eq!(kinds("//// not doc"), [TokenKind::LineComment]);
+        let block = || TokenKind::BlockComment { terminated: true };
+        assert_eq!(kinds("/**/"), [block()]);
+        assert_eq!(kinds("/***/"), [block()]);
+        assert_eq!(kinds("/*** not doc */"), [block()]);
+    }
+
+    #[test]
+    fn line_comments_end_before_crlf() {
+        assert_eq!(
+            kinds("/// doc\r\n//! inner\r\n"),
+            [
+                line_doc(DocStyle::Outer, " doc"),
+                TokenKind::Whitespace,
+                line_doc(DocStyle::Inner, " inner"),
+                TokenKind::Whitespace,
+            ]
+        );
+        // a lone '\r' does not end the line
+        assert_eq!(
+            kinds("/// a\rb\n"),
+            [line_doc(DocStyle::Outer, " a\rb"), TokenKind::Whitespace]
+        );
+
+        let source = "#!/bin/rsc\r\n// comment\r\nx";
+        let (tokens, _) = tokenize(source);
+        let texts: Vec<_> = tokens
+            .iter()
+            .map(|token| {
+                let chars = source.chars().skip(token.span.low);
+                chars.take(token.span.len()).collect::<String>()
+            })
+            .collect();
+        assert_eq!(texts, ["#!/bin/rsc", "\r\n", "// comment", "\r\n", "x"]);
+        assert_eq!(tokens[0].kind, TokenKind::Shebang);
+        assert_eq!(tokens[2].kind, TokenKind::LineComment);
+    }
+}
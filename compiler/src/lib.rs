@@ -1,22 +1,94 @@
 // pub(crate) mod error;
+pub(crate) mod bigint;
+pub(crate) mod confusables;
+pub(crate) mod diagnostic;
 pub(crate) mod error;
+pub(crate) mod error_codes;
+pub(crate) mod fix;
+pub(crate) mod incremental;
+pub(crate) mod json_emitter;
 pub(crate) mod lexer;
+pub(crate) mod source_map;
+pub(crate) mod stream;
 pub(crate) mod token;
+pub(crate) mod trivia;
+
+pub use bigint::BigUint;
+pub use diagnostic::{Diagnostic, Emitter, Label, LintLevel, Severity, Suggestion};
+pub use error_codes::{ErrorCode, Explanation};
+pub use fix::{apply_suggestions, fix};
+pub use incremental::{relex, Relexed, TextEdit, TokenModes};
+pub use json_emitter::JsonEmitter;
+pub use lexer::Lexer;
+pub use source_map::{FileId, Location, SourceFile, SourceMap, SpanLocator};
+pub use stream::StreamLexer;
+pub use token::{
+    Base, DocStyle, IntSuffix, KeywordKind, LiteralKind, Span, StrForm, Suffix, TemplatePart,
+    Token, TokenKind, OPERATORS,
+};
+pub use trivia::{to_source, SyntaxToken, Trivia};
 
 use error::LErrorHandler;
-use lexer::Lexer;
-use token::TokenKind;
+
+/// How diagnostics are reported
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum ErrorFormat {
+    /// coloured ariadne reports
+    #[default]
+    Human,
+    /// one JSON object per line on stderr, for tools to parse
+    Json,
+}
 
 pub fn compile(code: &str, file_name: &str) {
-    let err_handler = LErrorHandler::new(code, file_name);
-    let mut lexer = Lexer::new(code, &err_handler);
-    loop {
-        let tok = lexer.next_token();
-        if tok.kind == TokenKind::Eof {
-            break;
+    let mut source_map = SourceMap::new();
+    let file = source_map.add(file_name, code);
+    compile_file(&source_map, file, ErrorFormat::Human, &[]);
+}
+
+/// Lexes a file of the map, reporting its errors and printing its tokens
+///
+/// script_args are the command-line arguments after the script's path, for
+/// the script to read once it is run.
+pub fn compile_file(
+    source_map: &SourceMap,
+    file: FileId,
+    format: ErrorFormat,
+    script_args: &[String],
+) {
+    let mut err_handler: Box<dyn Emitter> = match format {
+        ErrorFormat::Human => Box::new(LErrorHandler::new(source_map)),
+        ErrorFormat::Json => Box::new(JsonEmitter::new(source_map, std::io::stderr())),
+    };
+    let mut lexer = Lexer::new(&source_map.file(file).source, file);
+    while let Some(tok) = lexer.next() {
+        for diagnostic in lexer.take_diagnostics() {
+            err_handler.emit(&diagnostic);
         }
         if tok.kind != TokenKind::Whitespace {
             println!("{:?}", tok);
         }
     }
+    // TODO: pass script_args to the runtime once the tokens are run
+    let _ = script_args;
+}
+
+/// Lexes the whole source without printing anything
+///
+/// Every token up to but not including Eof is returned, whitespace and
+/// comments too, along with a diagnostic for each error token.
+pub fn tokenize(source: &str) -> (Vec<Token<'_>>, Vec<Diagnostic>) {
+    let mut lexer = Lexer::new(source, FileId::default());
+    let tokens = lexer.by_ref().collect();
+    (tokens, lexer.take_diagnostics())
+}
+
+/// Lexes the whole source into a lossless token stream
+///
+/// Whitespace and comments become trivia of the significant tokens and the
+/// stream ends with Eof, `to_source` turns it back into the exact source.
+pub fn tokenize_lossless(source: &str) -> (Vec<SyntaxToken<'_>>, Vec<Diagnostic>) {
+    let mut lexer = Lexer::new(source, FileId::default());
+    let tokens = trivia::lossless_tokens(&mut lexer, source);
+    (tokens, lexer.take_diagnostics())
 }
@@ -0,0 +1,256 @@
+use std::fs;
+use std::io;
+use std::ops::Range;
+use std::path::Path;
+
+/// Identifies a file loaded into a `SourceMap`
+///
+/// The default id is the one used by `tokenize`, which lexes a source that
+/// does not belong to any map, and is also the id of the first added file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct FileId(u32);
+
+impl FileId {
+    pub fn index(&self) -> usize {
+        self.0 as usize
+    }
+}
+
+/// Resolves the character offsets of spans, e.g. for an emitter
+pub trait SpanLocator {
+    fn file_name(&self, file: FileId) -> &str;
+
+    /// None for an offset whose text is not known any more
+    fn locate(&self, file: FileId, offset: usize) -> Option<Location>;
+}
+
+/// Owns every loaded source file and resolves span offsets in them
+#[derive(Debug, Default)]
+pub struct SourceMap {
+    files: Vec<SourceFile>,
+}
+
+#[derive(Debug)]
+pub struct SourceFile {
+    pub name: String,
+    pub source: String,
+    // (character offset, byte offset) where each line starts
+    line_starts: Vec<(usize, usize)>,
+    // number of characters, which is the offset of the end of file
+    char_len: usize,
+}
+
+/// Position of a character offset, lines and columns are zero based
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Location {
+    pub line: usize,
+    /// column counted in characters
+    pub column: usize,
+    /// column counted in UTF-8 bytes
+    pub byte_column: usize,
+    /// column counted in UTF-16 code units, as used by editors
+    pub utf16_column: usize,
+    /// offset from the start of the file in UTF-8 bytes
+    pub byte_offset: usize,
+}
+
+/// Byte offset of a character offset in text, its length for offsets past
+/// the end
+pub(crate) fn byte_offset(text: &str, offset: usize) -> usize {
+    text.char_indices()
+        .nth(offset)
+        .map_or(text.len(), |(byte, _)| byte)
+}
+
+impl SourceFile {
+    pub fn new(name: impl Into<String>, source: impl Into<String>) -> SourceFile {
+        let (name, source) = (name.into(), source.into());
+        let mut line_starts = vec![(0, 0)];
+        let mut char_len = 0;
+        for (byte, ch) in source.char_indices() {
+            char_len += 1;
+            if ch == '\n' {
+                line_starts.push((char_len, byte + 1));
+            }
+        }
+        SourceFile {
+            name,
+            source,
+            line_starts,
+            char_len,
+        }
+    }
+
+    /// Character offset of the end of the file, where Eof is placed
+    pub fn end(&self) -> usize {
+        self.char_len
+    }
+
+    /// Byte offset of a character offset, only the characters of its line
+    /// are counted
+    pub fn byte_offset(&self, offset: usize) -> usize {
+        let (line_char, line_byte) = self.line_starts[self.line(offset)];
+        line_byte + byte_offset(&self.source[line_byte..], offset - line_char)
+    }
+
+    /// Text of a range of characters
+    pub fn slice(&self, range: Range<usize>) -> &str {
+        let low = self.byte_offset(range.start);
+        &self.source[low..self.byte_offset(range.end)]
+    }
+
+    /// Resolves a character offset, offsets past the end are clamped to it
+    pub fn location(&self, offset: usize) -> Location {
+        let offset = offset.min(self.char_len);
+        let line = self.line(offset);
+        let (line_char, line_byte) = self.line_starts[line];
+        let column = offset - line_char;
+        let (byte_column, utf16_column) = self.source[line_byte..]
+            .chars()
+            .take(column)
+            .fold((0, 0), |(bytes, units), ch| {
+                (bytes + ch.len_utf8(), units + ch.len_utf16())
+            });
+        Location {
+            line,
+            column,
+            byte_column,
+            utf16_column,
+            byte_offset: line_byte + byte_column,
+        }
+    }
+
+    // zero based line of a character offset
+    fn line(&self, offset: usize) -> usize {
+        self.line_starts
+            .partition_point(|&(start, _)| start <= offset)
+            - 1
+    }
+}
+
+impl SourceMap {
+    pub fn new() -> SourceMap {
+        SourceMap::default()
+    }
+
+    pub fn add(&mut self, name: impl Into<String>, source: impl Into<String>) -> FileId {
+        let id = FileId(self.files.len() as u32);
+        self.files.push(SourceFile::new(name, source));
+        id
+    }
+
+    /// Reads a file from disk and adds it under its path
+    pub fn load(&mut self, path: &Path) -> io::Result<FileId> {
+        let source = fs::read_to_string(path)?;
+        Ok(self.add(path.to_string_lossy(), source))
+    }
+
+    pub fn file(&self, id: FileId) -> &SourceFile {
+        &self.files[id.index()]
+    }
+
+    pub fn files(&self) -> impl Iterator<Item = (FileId, &SourceFile)> {
+        self.files
+            .iter()
+            .enumerate()
+            .map(|(index, file)| (FileId(index as u32), file))
+    }
+
+    pub fn location(&self, id: FileId, offset: usize) -> Location {
+        self.file(id).location(offset)
+    }
+}
+
+impl SpanLocator for SourceMap {
+    fn file_name(&self, file: FileId) -> &str {
+        &self.file(file).name
+    }
+
+    fn locate(&self, file: FileId, offset: usize) -> Option<Location> {
+        Some(self.location(file, offset))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Location, SourceFile, SourceMap};
+
+    #[test]
+    fn columns_count_characters_bytes_and_utf16_units() {
+        let mut source_map = SourceMap::new();
+        let file = source_map.add("a.rsc", "aé😀b\n日x");
+        let location = |offset| source_map.location(file, offset);
+
+        // 'b' after one, two and four byte characters, the emoji is a
+        // surrogate pair in UTF-16
+        assert_eq!(
+            location(3),
+            Location {
+                line: 0,
+                column: 3,
+                byte_column: 7,
+                utf16_column: 4,
+                byte_offset: 7,
+            }
+        );
+        assert_eq!(location(4).column, 4);
+        assert_eq!(
+            location(6),
+            Location {
+                line: 1,
+                column: 1,
+                byte_column: 3,
+                utf16_column: 1,
+                byte_offset: 12,
+            }
+        );
+        assert_eq!(location(0), Location::default());
+    }
+
+    #[test]
+    fn line_starts_after_each_line_feed() {
+        let mut source_map = SourceMap::new();
+        let file = source_map.add("a.rsc", "a\r\n\nb");
+        let location = |offset| source_map.location(file, offset);
+
+        // '\r' is the last character of its line
+        assert_eq!((location(1).line, location(1).column), (0, 1));
+        assert_eq!((location(2).line, location(2).column), (0, 2));
+        assert_eq!((location(3).line, location(3).column), (1, 0));
+        assert_eq!((location(4).line, location(4).byte_offset), (2, 4));
+    }
+
+    #[test]
+    fn offsets_past_the_end_are_clamped() {
+        let mut source_map = SourceMap::new();
+        let file = source_map.add("a.rsc", "ab\n");
+        assert_eq!(source_map.file(file).end(), 3);
+        assert_eq!(source_map.location(file, 99), source_map.location(file, 3));
+        assert_eq!(source_map.location(file, 3).line, 1);
+    }
+
+    #[test]
+    fn files_keep_their_ids() {
+        let mut source_map = SourceMap::new();
+        let first = source_map.add("a.rsc", "a");
+        let second = source_map.add("b.rsc", "\nb");
+        assert_eq!(first, Default::default());
+        assert_eq!(source_map.file(second).name, "b.rsc");
+        assert_eq!(source_map.location(second, 1).line, 1);
+        let names: Vec<_> = source_map
+            .files()
+            .map(|(id, file)| (id, file.name.as_str()))
+            .collect();
+        assert_eq!(names, [(first, "a.rsc"), (second, "b.rsc")]);
+    }
+
+    #[test]
+    fn byte_offsets_of_characters() {
+        let file = SourceFile::new("a.rsc", "aé\n😀b\r\n日");
+        let bytes: Vec<_> = (0..=9).map(|offset| file.byte_offset(offset)).collect();
+        assert_eq!(bytes, [0, 1, 3, 4, 8, 9, 10, 11, 14, 14]);
+        assert_eq!(file.slice(1..5), "é\n😀b");
+        assert_eq!(file.slice(7..8), "日");
+        assert_eq!(file.slice(8..8), "");
+    }
+}
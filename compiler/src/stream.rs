@@ -0,0 +1,289 @@
+use std::collections::VecDeque;
+use std::io::{self, ErrorKind, Read};
+use std::str;
+
+use crate::confusables::IdentLint;
+use crate::diagnostic::{Diagnostic, LintLevel};
+use crate::lexer::{Lexer, Mode, LOOKAHEAD};
+use crate::source_map::{FileId, Location};
+use crate::token::{Token, TokenKind};
+
+// bytes read from the reader at a time
+const CHUNK: usize = 64 * 1024;
+
+// distinct identifiers remembered to find confusable ones
+const IDENT_LIMIT: usize = 100_000;
+
+/// Lexer reading its source from a reader a chunk at a time
+///
+/// Only the text which is not lexed yet and the last tokens returned are
+/// kept in memory, so the memory used is bounded by the chunk size and the
+/// longest token rather than by the size of the input. Tokens own their
+/// text, and their spans are character offsets from the start of the input
+/// just like the ones of `Lexer`.
+///
+/// Only the first hundred thousand distinct identifiers are remembered to
+/// find confusable ones, later identifiers are checked against those each
+/// time they are used.
+pub struct StreamLexer<R: Read> {
+    reader: R,
+    file: FileId,
+    // decoded text, from the start of the last tokens returned
+    buffer: String,
+    // bytes of buffer which are lexed already
+    consumed: usize,
+    // character offset in the file where buffer starts and where its first
+    // unlexed character is
+    buffer_offset: usize,
+    offset: usize,
+    // location of the start of buffer
+    buffer_location: Location,
+    // end of a UTF-8 sequence cut by the end of the last chunk read
+    partial: Vec<u8>,
+    eof: bool,
+    // the last token in the buffer needs more text to be lexed
+    starved: bool,
+    modes: Vec<Mode>,
+    ident_lint: IdentLint,
+    bidi_level: LintLevel,
+    // lexed tokens not returned yet, with their diagnostics
+    tokens: VecDeque<(Token<'static>, Vec<Diagnostic>)>,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl<R: Read> StreamLexer<R> {
+    pub fn new(reader: R, file: FileId) -> StreamLexer<R> {
+        StreamLexer {
+            reader,
+            file,
+            buffer: String::new(),
+            consumed: 0,
+            buffer_offset: 0,
+            offset: 0,
+            buffer_location: Location::default(),
+            partial: Vec::new(),
+            eof: false,
+            starved: true,
+            modes: Vec::new(),
+            ident_lint: IdentLint::with_limit(IDENT_LIMIT),
+            bidi_level: LintLevel::Deny,
+            tokens: VecDeque::new(),
+            diagnostics: Vec::new(),
+        }
+    }
+
+    /// Same as `Lexer::with_bidi_level`
+    pub fn with_bidi_level(mut self, level: LintLevel) -> StreamLexer<R> {
+        self.bidi_level = level;
+        self
+    }
+
+    /// Same as `Lexer::next_token`, Eof is returned again once the input
+    /// is exhausted
+    ///
+    /// Errors are the ones of the reader, and invalid UTF-8 in the input.
+    pub fn next_token(&mut self) -> io::Result<Token<'static>> {
+        while self.tokens.is_empty() {
+            if self.starved {
+                self.fill()?;
+            }
+            self.lex_buffered();
+        }
+        let (token, diagnostics) = self.tokens.pop_front().unwrap();
+        self.diagnostics.extend(diagnostics);
+        if token.kind == TokenKind::Eof {
+            self.tokens.push_front((token.clone(), Vec::new()));
+        }
+        Ok(token)
+    }
+
+    /// Diagnostics of the error tokens returned since the last call
+    pub fn take_diagnostics(&mut self) -> Vec<Diagnostic> {
+        std::mem::take(&mut self.diagnostics)
+    }
+
+    /// Resolves a character offset in the tokens returned since the last
+    /// chunk was read, None for an offset in text which is dropped already
+    ///
+    /// The spans of the diagnostics taken after each token can be resolved
+    /// before the next one is lexed, except for an unterminated "${" whose
+    /// template string started further back.
+    pub fn location(&self, offset: usize) -> Option<Location> {
+        let skip = offset.checked_sub(self.buffer_offset)?;
+        let mut location = self.buffer_location;
+        let mut chars = self.buffer.chars();
+        for _ in 0..skip {
+            advance(&mut location, chars.next()?);
+        }
+        Some(location)
+    }
+
+    // Lexes the tokens which are complete in the buffer, stopping at one
+    // which could go on in the text not read yet
+    fn lex_buffered(&mut self) {
+        let text = &self.buffer[self.consumed..];
+        let mut lexer = Lexer::continuing(
+            text,
+            self.file,
+            self.offset,
+            std::mem::take(&mut self.modes),
+            std::mem::take(&mut self.ident_lint),
+        )
+        .with_bidi_level(self.bidi_level);
+        let mut modes = lexer.modes().to_vec();
+        let mut lexed = 0;
+        loop {
+            let token = lexer.lex_token();
+            let rest = &text[lexer.byte_position()..];
+            // lexing peeked past the end of the buffer, the token is lexed
+            // again from its start once there is more text
+            let cut = !self.eof
+                && (matches!(
+                    token.kind,
+                    TokenKind::Eof | TokenKind::UnterminatedInterpolation
+                ) || rest.chars().nth(LOOKAHEAD - 1).is_none()
+                    || rest.trim_start_matches('#').is_empty());
+            if cut {
+                self.starved = true;
+                break;
+            }
+            lexer.error_report(&token);
+            modes = lexer.modes().to_vec();
+            lexed = lexer.byte_position();
+            self.offset = lexer.char_position();
+            let eof = token.kind == TokenKind::Eof;
+            self.tokens
+                .push_back((token.into_owned(), lexer.take_diagnostics()));
+            if eof {
+                break;
+            }
+        }
+        let (_, ident_lint) = lexer.into_state();
+        self.consumed += lexed;
+        self.modes = modes;
+        self.ident_lint = ident_lint;
+    }
+
+    // Drops the text of the returned tokens and reads at least another chunk,
+    // or as much as is buffered so a long token is not lexed too many times
+    fn fill(&mut self) -> io::Result<()> {
+        for ch in self.buffer[..self.consumed].chars() {
+            advance(&mut self.buffer_location, ch);
+        }
+        self.buffer.drain(..self.consumed);
+        self.buffer_offset = self.offset;
+        self.consumed = 0;
+
+        let wanted = self.buffer.len().max(CHUNK);
+        let mut bytes = std::mem::take(&mut self.partial);
+        let mut chunk = vec![0; CHUNK];
+        while bytes.len() < wanted {
+            match self.reader.read(&mut chunk) {
+                Ok(0) => {
+                    self.eof = true;
+                    break;
+                }
+                Ok(read) => bytes.extend_from_slice(&chunk[..read]),
+                Err(e) if e.kind() == ErrorKind::Interrupted => {}
+                Err(e) => return Err(e),
+            }
+        }
+        let valid = match str::from_utf8(&bytes) {
+            Ok(text) => text.len(),
+            // a sequence cut by the end of the chunk is completed by the next
+            Err(e) if e.error_len().is_none() && !self.eof => e.valid_up_to(),
+            Err(_) => {
+                return Err(io::Error::new(
+                    ErrorKind::InvalidData,
+                    "stream did not contain valid UTF-8",
+                ))
+            }
+        };
+        self.partial = bytes.split_off(valid);
+        self.buffer.push_str(str::from_utf8(&bytes).unwrap());
+        self.starved = false;
+        Ok(())
+    }
+}
+
+impl<R: Read> Iterator for StreamLexer<R> {
+    type Item = io::Result<Token<'static>>;
+
+    /// Same as `next_token` but the iteration ends at Eof instead of
+    /// yielding it
+    fn next(&mut self) -> Option<io::Result<Token<'static>>> {
+        match self.next_token() {
+            Ok(token) if token.kind == TokenKind::Eof => None,
+            result => Some(result),
+        }
+    }
+}
+
+// Moves a location past one character
+fn advance(location: &mut Location, ch: char) {
+    location.byte_offset += ch.len_utf8();
+    if ch == '\n' {
+        location.line += 1;
+        (location.column, location.byte_column, location.utf16_column) = (0, 0, 0);
+        return;
+    }
+    location.column += 1;
+    location.byte_column += ch.len_utf8();
+    location.utf16_column += ch.len_utf16();
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{self, Read};
+
+    use super::{StreamLexer, CHUNK};
+    use crate::source_map::FileId;
+    use crate::token::TokenKind;
+    use crate::tokenize;
+
+    // Reader returning at most a few bytes at a time, which cuts UTF-8
+    // sequences and tokens anywhere
+    struct ShortReads<'a>(&'a [u8]);
+
+    impl Read for ShortReads<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            let read = buf.len().min(self.0.len()).min(7);
+            buf[..read].copy_from_slice(&self.0[..read]);
+            self.0 = &self.0[read..];
+            Ok(read)
+        }
+    }
+
+    #[test]
+    fn streamed_tokens_match_tokenize() {
+        let snippet = "let café = `日本 ${a + `😀 ${b}`} \\q`; // x\u{202E}y\r\n\
+                       /* a /* b */ c */ r#\"raw \"\"#.len() 0xFFu 1__0 pаth path\n";
+        let mut source = String::new();
+        while source.len() <= CHUNK {
+            source.push_str(snippet);
+        }
+        // a token longer than a chunk, and an interpolation left open
+        source.push_str(&format!("/* {} */ `${{", "é".repeat(CHUNK)));
+        source.push_str(snippet);
+
+        let (tokens, diagnostics) = tokenize(&source);
+        let mut lexer = StreamLexer::new(ShortReads(source.as_bytes()), FileId::default());
+        let mut streamed = Vec::new();
+        let mut streamed_diagnostics = Vec::new();
+        loop {
+            let token = lexer.next_token().unwrap();
+            streamed_diagnostics.extend(lexer.take_diagnostics());
+            if token.kind == TokenKind::Eof {
+                break;
+            }
+            streamed.push((token.kind, token.span));
+        }
+        let expected: Vec<_> = tokens
+            .into_iter()
+            .map(|token| (token.kind.into_owned(), token.span))
+            .collect();
+        assert_eq!(streamed, expected);
+        assert_eq!(streamed_diagnostics, diagnostics);
+        assert!(!diagnostics.is_empty());
+    }
+}
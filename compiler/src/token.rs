@@ -1,13 +1,17 @@
-#[derive(Debug)]
-pub struct Token {
+use crate::bigint::BigUint;
+use crate::source_map::FileId;
+use std::borrow::Cow;
+
+#[derive(Debug, Clone)]
+pub struct Token<'a> {
     // literal: &'a str,
-    pub kind: TokenKind, // temporary pub
+    pub kind: TokenKind<'a>, // temporary pub
     // pub len: usize,
-    pub span: Span, // might have to add file path/name later on
+    pub span: Span,
 }
 
-impl Token {
-    pub fn new(kind: TokenKind, span: Span) -> Token {
+impl<'a> Token<'a> {
+    pub fn new(kind: TokenKind<'a>, span: Span) -> Token<'a> {
         //len: usize,
         Token {
             // literal,
@@ -17,6 +21,12 @@ impl Token {
         }
     }
 
+    /// Same token without borrowing from the source, e.g. to keep it after
+    /// the source is gone
+    pub fn into_owned(self) -> Token<'static> {
+        Token::new(self.kind.into_owned(), self.span)
+    }
+
     // pub fn is_keyword(literal: &str) -> bool {
     //     false
     // }
@@ -53,20 +63,96 @@ impl Token {
     }
 }
 
-#[derive(Debug)]
-pub(crate) struct Span {
+/// Operators and punctuation with their token, the lexer takes the longest
+/// one the source starts with, so adding an operator only takes an entry
+///
+/// '/' starting a comment and the braces and '`' of template strings are
+/// lexed separately. Incremental and streaming lexing assume no operator is
+/// longer than three characters.
+pub const OPERATORS: &[(&str, TokenKind<'static>)] = &[
+    (";", TokenKind::Semi),
+    (":", TokenKind::Colon),
+    ("::", TokenKind::Scope),
+    (",", TokenKind::Comma),
+    (".", TokenKind::Dot),
+    ("..", TokenKind::DotDot),
+    ("..=", TokenKind::DotDotEq),
+    ("...", TokenKind::Ellipsis),
+    ("(", TokenKind::OpenPara),
+    (")", TokenKind::ClosePara),
+    ("[", TokenKind::OpenBracket),
+    ("]", TokenKind::CloseBracket),
+    ("@", TokenKind::At),
+    ("#", TokenKind::Sharp),
+    ("~", TokenKind::Tilde),
+    ("$", TokenKind::Dollar),
+    ("?", TokenKind::Question),
+    ("??", TokenKind::Coalesce),
+    ("?.", TokenKind::OptionalDot),
+    ("=", TokenKind::Eq),
+    ("==", TokenKind::EqEq),
+    ("=>", TokenKind::FatArrow),
+    ("!", TokenKind::Bang),
+    ("!=", TokenKind::NotEq),
+    ("<", TokenKind::Lt),
+    ("<=", TokenKind::LtEq),
+    ("<<", TokenKind::Shl),
+    ("<<=", TokenKind::ShlEq),
+    (">", TokenKind::Gt),
+    (">=", TokenKind::GtEq),
+    (">>", TokenKind::Shr),
+    (">>=", TokenKind::ShrEq),
+    ("+", TokenKind::Plus),
+    ("+=", TokenKind::PlusEq),
+    ("++", TokenKind::Increment),
+    ("-", TokenKind::Minus),
+    ("-=", TokenKind::MinusEq),
+    ("--", TokenKind::Decrement),
+    ("->", TokenKind::Arrow),
+    ("*", TokenKind::Asterisk),
+    ("*=", TokenKind::AsteriskEq),
+    ("**", TokenKind::Power),
+    ("/", TokenKind::Slash),
+    ("/=", TokenKind::SlashEq),
+    ("%", TokenKind::Percent),
+    ("%=", TokenKind::PercentEq),
+    ("^", TokenKind::Caret),
+    ("^=", TokenKind::CaretEq),
+    ("&", TokenKind::Ampersand),
+    ("&=", TokenKind::AmpersandEq),
+    ("&&", TokenKind::And),
+    ("|", TokenKind::Pipe),
+    ("|=", TokenKind::PipeEq),
+    ("||", TokenKind::Or),
+    ("|>", TokenKind::PipeGt),
+];
+
+/// Character offsets of a token in a file, low is inclusive and high is
+/// exclusive, a `SourceMap` resolves them into lines and columns
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Span {
+    pub file: FileId,
     pub low: usize,
     pub high: usize,
 }
 
 impl Span {
-    pub(crate) fn set(low: usize, high: usize) -> Span {
-        Span { low, high }
+    pub fn set(file: FileId, low: usize, high: usize) -> Span {
+        Span { file, low, high }
+    }
+
+    /// Number of characters covered
+    pub fn len(&self) -> usize {
+        self.high - self.low
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.low == self.high
     }
 }
 
-#[derive(PartialEq, Debug)]
-pub enum TokenKind {
+#[derive(PartialEq, Debug, Clone)]
+pub enum TokenKind<'a> {
     // Multi Character Tokens
     /// "// comment"
     // TODO: Check how Javascript documentation works
@@ -78,16 +164,39 @@ pub enum TokenKind {
         terminated: bool,
     },
 
+    /// "/// doc" or "//! doc"
+    /// text is everything after the "///" or "//!" up to the end of line,
+    /// without the "\r" of a "\r\n"
+    LineDocComment {
+        style: DocStyle,
+        text: Cow<'a, str>,
+    },
+
+    /// "/** doc */" or "/*! doc */"
+    /// text is everything between the "/**" or "/*!" and the closing "*/"
+    BlockDocComment {
+        style: DocStyle,
+        text: Cow<'a, str>,
+        terminated: bool,
+    },
+
     /// " "
     /// All Whitespace characters
     Whitespace,
 
+    /// "#!/usr/bin/env rsc"
+    /// only on the first line of a file, up to the end of that line
+    Shebang,
+
     /// Ident or Keyword
     ///
     /// example: "x", "let"  
     /// All Ident and Keywords are considered Ident
+    ///
+    /// name is NFC normalized, so the same identifier written with
+    /// precomposed or combining characters has the same name
     Ident {
-        name: String,
+        name: Cow<'a, str>,
     },
 
     Keyword {
@@ -105,13 +214,78 @@ pub enum TokenKind {
     /// '_' inbetween shall be ignored, if placed inbetween digits
     /// but suffix shall not start with '_'
     Literal {
-        kind: LiteralKind,
+        kind: LiteralKind<'a>,
         // suffix_start: u32,
     },
 
+    /// "`Hello ${", "} and ${", "} World`" or just "`Hello World`"
+    ///
+    /// Parts of a template string, a parser joins the parts and the
+    /// expressions between them into a string concatenation
+    Template {
+        part: TemplatePart,
+        terminated: bool,
+        value: Cow<'a, str>,
+        raw: Cow<'a, str>,
+    },
+
+    /// "${" of a template string which is never closed with "}"
+    UnterminatedInterpolation,
+
     InvalidDecimal,
     InvalidExponent,
 
+    /// "12x", "1.9u", "0b1f"
+    /// offset is the character position where the suffix starts
+    /// relative to the start of the literal
+    InvalidSuffix {
+        offset: usize,
+    },
+
+    /// "\q", "\xFF", "\u{110000}" inside a string
+    /// offset is the character position of the '\' relative to the start
+    /// of the string and length is the number of characters in the escape
+    InvalidEscape {
+        offset: usize,
+        length: usize,
+    },
+
+    /// "r#"abc"##" or an unterminated "r##"abc"#"
+    /// offset is the character position of the closing '"' relative to the
+    /// start of the string, found is the number of '#' after it
+    MismatchedRawStr {
+        expected: usize,
+        found: usize,
+        offset: usize,
+    },
+
+    /// "99999999999999999999", integer literal too large for an int, or
+    /// for a uint with the "u" suffix
+    /// the "n" suffix should be used for such literals instead
+    IntOverflow {
+        suffix: Option<Suffix>,
+    },
+
+    /// "1__000", "1_", "0x_FF", "1_.5", "1e_5"
+    /// offset is the character position of the first misplaced '_'
+    /// relative to the start of the literal
+    InvalidSeparator {
+        offset: usize,
+    },
+
+    /// "0x", "0o", "0b" with no digits after the prefix
+    EmptyInt {
+        base: Base,
+    },
+
+    /// "0b102", "0o78"
+    /// offset is the character position of the first invalid digit
+    /// relative to the start of the literal
+    InvalidDigit {
+        base: Base,
+        offset: usize,
+    },
+
     /// "::"
     Scope,
     /// "->"
@@ -141,6 +315,39 @@ pub enum TokenKind {
     PercentEq,
     /// "^="
     CaretEq,
+    /// "&="
+    AmpersandEq,
+    /// "|="
+    PipeEq,
+    /// "<<="
+    ShlEq,
+    /// ">>="
+    ShrEq,
+
+    /// "**"
+    Power,
+    /// "<<"
+    Shl,
+    /// ">>"
+    Shr,
+    /// "=>"
+    FatArrow,
+    /// ".."
+    DotDot,
+    /// "..="
+    DotDotEq,
+    /// "..."
+    Ellipsis,
+    /// "??"
+    Coalesce,
+    /// "?."
+    OptionalDot,
+    /// "++"
+    Increment,
+    /// "--"
+    Decrement,
+    /// "|>"
+    PipeGt,
 
     // One Character Tokens
     /// ";"
@@ -174,8 +381,6 @@ pub enum TokenKind {
     BSlash,
     /// "~"
     Tilde,
-    /// "`"
-    Btick,
     /// "!"
     Bang,
     /// "@"
@@ -212,38 +417,312 @@ pub enum TokenKind {
     Eof,
 }
 
-#[derive(Debug, PartialEq)]
-pub enum LiteralKind {
+impl TokenKind<'_> {
+    /// Whitespace, plain comments and the shebang, which carry no meaning
+    /// for the parser, doc comments are not trivia since they document the
+    /// next item
+    pub fn is_trivia(&self) -> bool {
+        matches!(
+            self,
+            TokenKind::Whitespace
+                | TokenKind::Shebang
+                | TokenKind::LineComment
+                | TokenKind::BlockComment { .. }
+        )
+    }
+
+    pub fn into_owned(self) -> TokenKind<'static> {
+        let owned = |text: Cow<str>| Cow::Owned(text.into_owned());
+        match self {
+            TokenKind::LineDocComment { style, text } => TokenKind::LineDocComment {
+                style,
+                text: owned(text),
+            },
+            TokenKind::BlockDocComment {
+                style,
+                text,
+                terminated,
+            } => TokenKind::BlockDocComment {
+                style,
+                text: owned(text),
+                terminated,
+            },
+            TokenKind::Ident { name } => TokenKind::Ident { name: owned(name) },
+            TokenKind::Literal { kind } => TokenKind::Literal {
+                kind: kind.into_owned(),
+            },
+            TokenKind::Template {
+                part,
+                terminated,
+                value,
+                raw,
+            } => TokenKind::Template {
+                part,
+                terminated,
+                value: owned(value),
+                raw: owned(raw),
+            },
+            TokenKind::LineComment => TokenKind::LineComment,
+            TokenKind::BlockComment { terminated } => TokenKind::BlockComment { terminated },
+            TokenKind::Whitespace => TokenKind::Whitespace,
+            TokenKind::Shebang => TokenKind::Shebang,
+            TokenKind::Keyword { kind } => TokenKind::Keyword { kind },
+            TokenKind::InvalidIdent => TokenKind::InvalidIdent,
+            TokenKind::UnterminatedInterpolation => TokenKind::UnterminatedInterpolation,
+            TokenKind::InvalidDecimal => TokenKind::InvalidDecimal,
+            TokenKind::InvalidExponent => TokenKind::InvalidExponent,
+            TokenKind::InvalidSuffix { offset } => TokenKind::InvalidSuffix { offset },
+            TokenKind::InvalidEscape { offset, length } => {
+                TokenKind::InvalidEscape { offset, length }
+            }
+            TokenKind::MismatchedRawStr {
+                expected,
+                found,
+                offset,
+            } => TokenKind::MismatchedRawStr {
+                expected,
+                found,
+                offset,
+            },
+            TokenKind::IntOverflow { suffix } => TokenKind::IntOverflow { suffix },
+            TokenKind::InvalidSeparator { offset } => TokenKind::InvalidSeparator { offset },
+            TokenKind::EmptyInt { base } => TokenKind::EmptyInt { base },
+            TokenKind::InvalidDigit { base, offset } => TokenKind::InvalidDigit { base, offset },
+            TokenKind::Scope => TokenKind::Scope,
+            TokenKind::Arrow => TokenKind::Arrow,
+            TokenKind::Or => TokenKind::Or,
+            TokenKind::And => TokenKind::And,
+            TokenKind::NotEq => TokenKind::NotEq,
+            TokenKind::EqEq => TokenKind::EqEq,
+            TokenKind::GtEq => TokenKind::GtEq,
+            TokenKind::LtEq => TokenKind::LtEq,
+            TokenKind::PlusEq => TokenKind::PlusEq,
+            TokenKind::MinusEq => TokenKind::MinusEq,
+            TokenKind::AsteriskEq => TokenKind::AsteriskEq,
+            TokenKind::SlashEq => TokenKind::SlashEq,
+            TokenKind::PercentEq => TokenKind::PercentEq,
+            TokenKind::CaretEq => TokenKind::CaretEq,
+            TokenKind::AmpersandEq => TokenKind::AmpersandEq,
+            TokenKind::PipeEq => TokenKind::PipeEq,
+            TokenKind::ShlEq => TokenKind::ShlEq,
+            TokenKind::ShrEq => TokenKind::ShrEq,
+            TokenKind::Power => TokenKind::Power,
+            TokenKind::Shl => TokenKind::Shl,
+            TokenKind::Shr => TokenKind::Shr,
+            TokenKind::FatArrow => TokenKind::FatArrow,
+            TokenKind::DotDot => TokenKind::DotDot,
+            TokenKind::DotDotEq => TokenKind::DotDotEq,
+            TokenKind::Ellipsis => TokenKind::Ellipsis,
+            TokenKind::Coalesce => TokenKind::Coalesce,
+            TokenKind::OptionalDot => TokenKind::OptionalDot,
+            TokenKind::Increment => TokenKind::Increment,
+            TokenKind::Decrement => TokenKind::Decrement,
+            TokenKind::PipeGt => TokenKind::PipeGt,
+            TokenKind::Semi => TokenKind::Semi,
+            TokenKind::Colon => TokenKind::Colon,
+            TokenKind::Comma => TokenKind::Comma,
+            TokenKind::Dot => TokenKind::Dot,
+            TokenKind::Slash => TokenKind::Slash,
+            TokenKind::Question => TokenKind::Question,
+            TokenKind::Lt => TokenKind::Lt,
+            TokenKind::Gt => TokenKind::Gt,
+            TokenKind::OpenBrace => TokenKind::OpenBrace,
+            TokenKind::CloseBrace => TokenKind::CloseBrace,
+            TokenKind::OpenBracket => TokenKind::OpenBracket,
+            TokenKind::CloseBracket => TokenKind::CloseBracket,
+            TokenKind::Pipe => TokenKind::Pipe,
+            TokenKind::BSlash => TokenKind::BSlash,
+            TokenKind::Tilde => TokenKind::Tilde,
+            TokenKind::Bang => TokenKind::Bang,
+            TokenKind::At => TokenKind::At,
+            TokenKind::Sharp => TokenKind::Sharp,
+            TokenKind::Dollar => TokenKind::Dollar,
+            TokenKind::Percent => TokenKind::Percent,
+            TokenKind::Caret => TokenKind::Caret,
+            TokenKind::Ampersand => TokenKind::Ampersand,
+            TokenKind::Asterisk => TokenKind::Asterisk,
+            TokenKind::OpenPara => TokenKind::OpenPara,
+            TokenKind::ClosePara => TokenKind::ClosePara,
+            TokenKind::Minus => TokenKind::Minus,
+            TokenKind::Plus => TokenKind::Plus,
+            TokenKind::Eq => TokenKind::Eq,
+            TokenKind::Unknown => TokenKind::Unknown,
+            TokenKind::Eof => TokenKind::Eof,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum LiteralKind<'a> {
     /// numbers with `\d+` are considered Int
+    /// "0x", "0o" and "0b" prefixes give hexadecimal, octal and binary
     Int {
-        // base: Base, // remove later
+        base: Base,
         value: isize,
-    }, // TODO: there is one more field called 'empty_int' not sure why its used
-    /// numbers with decimal or exponent are considered Float
+        suffix: Option<IntSuffix>,
+    },
+    /// Int with the "u" suffix, example: "18446744073709551615u"
+    /// these range up to the largest 64-bit unsigned integer
+    UInt { base: Base, value: u64 },
+    /// Int with the "n" suffix, example: "99999999999999999999n"
+    /// these are not limited to 64 bits
+    BigInt { base: Base, value: BigUint },
+    /// numbers with decimal or exponent, or the "f" suffix, are considered Float
     Float {
         // base: Base, // remove later
         value: f64,
+        suffix: Option<Suffix>,
     }, // TODO: there is one more field called 'empty_exponent' not sure why its used
+    /// value has the escape sequences decoded, raw is the text between
+    /// the quotes exactly as written in the source
+    /// value only owns its text when it differs from raw
+    /// a quoted string without its closing quote is unterminated at the end
+    /// of its line, the other forms at the end of the file
     Str {
         terminated: bool,
         start: char,
-        value: String,
+        value: Cow<'a, str>,
+        raw: Cow<'a, str>,
+        form: StrForm,
     },
 }
+impl LiteralKind<'_> {
+    pub fn into_owned(self) -> LiteralKind<'static> {
+        match self {
+            LiteralKind::Int {
+                base,
+                value,
+                suffix,
+            } => LiteralKind::Int {
+                base,
+                value,
+                suffix,
+            },
+            LiteralKind::UInt { base, value } => LiteralKind::UInt { base, value },
+            LiteralKind::BigInt { base, value } => LiteralKind::BigInt { base, value },
+            LiteralKind::Float { value, suffix } => LiteralKind::Float { value, suffix },
+            LiteralKind::Str {
+                terminated,
+                start,
+                value,
+                raw,
+                form,
+            } => LiteralKind::Str {
+                terminated,
+                start,
+                value: Cow::Owned(value.into_owned()),
+                raw: Cow::Owned(raw.into_owned()),
+                form,
+            },
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum DocStyle {
+    /// "///" and "/**", documents the function, struct or enum
+    /// declaration which follows the comment
+    Outer,
+    /// "//!" and "/*!", documents the enclosing item or the whole file
+    Inner,
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum StrForm {
+    /// "..." or '...' with escape sequences, on a single line
+    Quoted,
+    /// r"..." or r#"..."#, no escape sequences
+    Raw { hashes: usize },
+    /// """...""" spanning lines, no escape sequences, the common
+    /// indentation is stripped from the value and its lines end with '\n'
+    MultiLine,
+}
+
+impl StrForm {
+    /// Text which closes a string of this form
+    pub fn terminator(&self, start: char) -> String {
+        match self {
+            StrForm::Quoted => start.to_string(),
+            StrForm::Raw { hashes } => format!("\"{}", "#".repeat(*hashes)),
+            StrForm::MultiLine => "\"\"\"".to_string(),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum TemplatePart {
+    /// "`...`", no interpolation at all
+    Full,
+    /// "`...${"
+    Head,
+    /// "}...${"
+    Middle,
+    /// "}...`"
+    Tail,
+}
+
 // TODO: notice there is no true or false in literal, also no void
 // TODO: maybe add Complex type later if possible
 
-// #[derive(Debug, PartialEq)]
-// pub enum Base {
-//     Binary = 2,
-//     Octal = 8,
-//     /// no prefix for decimal
-//     Decimal = 10,
-//     Hexadecimal = 16,
-// }
-
-#[derive(PartialEq, Debug)]
-pub(crate) enum KeywordKind {
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Base {
+    /// "0b"
+    Binary = 2,
+    /// "0o"
+    Octal = 8,
+    /// no prefix for decimal
+    Decimal = 10,
+    /// "0x"
+    Hexadecimal = 16,
+}
+
+impl Base {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Base::Binary => "binary",
+            Base::Octal => "octal",
+            Base::Decimal => "decimal",
+            Base::Hexadecimal => "hexadecimal",
+        }
+    }
+}
+
+/// Type suffix of a numeric literal
+///
+/// "u", "i" and "n" are allowed on every Int, "f" only on decimal Int and Float
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Suffix {
+    /// "u", makes a 64-bit unsigned UInt
+    Unsigned,
+    /// "i"
+    Signed,
+    /// "f"
+    Float,
+    /// "n", makes an arbitrary-precision BigInt
+    Big,
+}
+
+/// Suffix kept by an Int, the other suffixes give a UInt, a BigInt or a Float
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum IntSuffix {
+    /// "i"
+    Signed,
+}
+
+impl Suffix {
+    pub fn from_literal(literal: &str) -> Option<Suffix> {
+        match literal {
+            "u" => Some(Suffix::Unsigned),
+            "i" => Some(Suffix::Signed),
+            "f" => Some(Suffix::Float),
+            "n" => Some(Suffix::Big),
+            _ => None,
+        }
+    }
+}
+
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum KeywordKind {
     Let,
     Const,
     Function,
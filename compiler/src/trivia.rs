@@ -0,0 +1,118 @@
+use std::fmt;
+
+use crate::lexer::Lexer;
+use crate::token::{Span, Token, TokenKind};
+
+/// Whitespace or a plain comment together with its source text
+#[derive(Debug, Clone, PartialEq)]
+pub struct Trivia<'a> {
+    pub kind: TokenKind<'a>,
+    pub span: Span,
+    pub text: &'a str,
+}
+
+/// Significant token of a lossless token stream
+///
+/// Trivia on the same line after the token is its trailing trivia, the rest
+/// up to the next significant token is that token's leading trivia. The
+/// stream always ends with an Eof token holding the trivia at the end of the
+/// file, so writing out every token reproduces the source byte for byte.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SyntaxToken<'a> {
+    pub leading: Vec<Trivia<'a>>,
+    pub kind: TokenKind<'a>,
+    pub span: Span,
+    /// source text of the token itself, without trivia
+    pub text: &'a str,
+    pub trailing: Vec<Trivia<'a>>,
+}
+
+impl<'a> SyntaxToken<'a> {
+    pub fn token(&self) -> Token<'a> {
+        Token::new(self.kind.clone(), self.span)
+    }
+}
+
+impl fmt::Display for SyntaxToken<'_> {
+    /// Writes the token with its trivia exactly as it is in the source
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for trivia in &self.leading {
+            f.write_str(trivia.text)?;
+        }
+        f.write_str(self.text)?;
+        for trivia in &self.trailing {
+            f.write_str(trivia.text)?;
+        }
+        Ok(())
+    }
+}
+
+/// Groups the lexer's tokens into significant tokens with their trivia
+pub(crate) fn lossless_tokens<'a>(lexer: &mut Lexer<'a>, source: &'a str) -> Vec<SyntaxToken<'a>> {
+    let mut tokens: Vec<SyntaxToken<'a>> = Vec::new();
+    let mut leading = Vec::new();
+    // trivia goes to the previous token until the line ends
+    let mut trailing_open = false;
+    loop {
+        let start = lexer.byte_position();
+        let token = lexer.next_token();
+        let text = &source[start..lexer.byte_position()];
+        if token.kind.is_trivia() {
+            let ends_line = text.contains('\n');
+            let trivia = Trivia {
+                kind: token.kind,
+                span: token.span,
+                text,
+            };
+            match tokens.last_mut() {
+                Some(previous) if trailing_open && !ends_line => previous.trailing.push(trivia),
+                _ => {
+                    trailing_open = false;
+                    leading.push(trivia);
+                }
+            }
+            continue;
+        }
+        let eof = token.kind == TokenKind::Eof;
+        tokens.push(SyntaxToken {
+            leading: std::mem::take(&mut leading),
+            kind: token.kind,
+            span: token.span,
+            text,
+            trailing: Vec::new(),
+        });
+        trailing_open = true;
+        if eof {
+            return tokens;
+        }
+    }
+}
+
+/// Writes a lossless token stream back out as source
+pub fn to_source(tokens: &[SyntaxToken]) -> String {
+    tokens.iter().map(ToString::to_string).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{to_source, tokenize_lossless};
+
+    #[test]
+    fn lossless_tokens_write_back_the_source() {
+        let sources = [
+            "",
+            "   \n\n",
+            "#!/usr/bin/env rsc\nlet a = 1; // one\n",
+            "let a = 1;\r\n\r\n// comment\r\nlet b = 2;  \r\n",
+            "//! module docs\n/// item docs\nlet a = 1;\n/** block docs */\n/*! inner */ let b;",
+            "let s = `a ${b + `c ${d} e`} ${ { f } } g`;\n",
+            "/* a /* nested */ comment */ x /* trailing */\n  y",
+            "let s = \"unterminated\nlet r = r#\"raw\"#; \t\u{202E} é\n",
+            "let t = `open ${a",
+        ];
+        for source in sources {
+            let (tokens, _) = tokenize_lossless(source);
+            assert_eq!(to_source(&tokens), source);
+        }
+    }
+}
@@ -2,24 +2,62 @@
 
 use std::env;
 use std::fs;
+use std::io::{self, Read};
 use std::path::Path;
 
-use compiler::compile;
+use compiler::{
+    compile_file, fix, Diagnostic, Emitter, ErrorCode, ErrorFormat, FileId, JsonEmitter, Location,
+    Severity, SourceMap, SpanLocator, StreamLexer, TokenKind,
+};
 
 fn main() {
     // repl::start();
 
     // Collect command-line arguments
     let args: Vec<String> = env::args().collect();
+    let usage = format!(
+        "Usage: {0} [--error-format=human|json] <file_path> [script_args...]\n       {0} fix <file_path>\n       {0} check <file_path>\n       {0} --explain <code>",
+        args[0]
+    );
 
-    // Ensure a file path is provided
-    if args.len() < 2 {
-        eprintln!("Usage: {} <file_path>", args[0]);
+    // `fix` rewrites the file with the suggested fixes instead of compiling it,
+    // `check` only reports its diagnostics without loading it whole
+    let fixing = args.get(1).is_some_and(|arg| arg == "fix");
+    let checking = args.get(1).is_some_and(|arg| arg == "check");
+
+    // Split the options from the file path, the arguments after the path
+    // belong to the script
+    let mut error_format = ErrorFormat::Human;
+    let mut file_path = None;
+    let mut rest = args[1 + (fixing || checking) as usize..].iter();
+    while let Some(arg) = rest.next() {
+        if arg == "--explain" {
+            explain(rest.next().map(String::as_str), &usage);
+        }
+        match arg.strip_prefix("--error-format=") {
+            Some("human") => error_format = ErrorFormat::Human,
+            Some("json") => error_format = ErrorFormat::Json,
+            Some(other) => {
+                eprintln!("Error: Unknown error format `{}`.\n{}", other, usage);
+                std::process::exit(1);
+            }
+            None => {
+                file_path = Some(arg);
+                break;
+            }
+        }
+    }
+    let script_args: Vec<String> = rest.cloned().collect();
+    if (fixing || checking) && !script_args.is_empty() {
+        eprintln!("{}", usage);
         std::process::exit(1);
     }
 
-    // The file path is the second argument
-    let file_path = &args[1];
+    // Ensure a file path is provided
+    let Some(file_path) = file_path else {
+        eprintln!("{}", usage);
+        std::process::exit(1);
+    };
 
     // Convert the string slice to a Path
     let path = Path::new(file_path);
@@ -29,13 +67,29 @@ fn main() {
         path.file_name(),
         path.extension().and_then(|ext| ext.to_str()),
     ) {
-        (Some(file_name), Some("rsc")) => {
+        // scripts run from a shell through their shebang have no extension
+        (Some(_), Some("rsc") | None) if checking => check(path, error_format),
+        (Some(file_name), Some("rsc") | None) => {
             // File has the correct extension; read the file contents
             match fs::read_to_string(path) {
+                Ok(contents) if fixing => {
+                    let (fixed, applied) = fix(&contents);
+                    if let Err(e) = fs::write(path, fixed) {
+                        eprintln!("Error: Failed to write the file: {}", e);
+                        std::process::exit(1);
+                    }
+                    println!(
+                        "Applied {} fixes to {}",
+                        applied,
+                        file_name.to_string_lossy()
+                    );
+                }
                 Ok(contents) => {
                     println!("File name: {}", file_name.to_string_lossy());
                     println!("File content:\n{}", contents);
-                    compile(&contents, file_name.to_str().unwrap());
+                    let mut source_map = SourceMap::new();
+                    let file = source_map.add(file_name.to_string_lossy(), contents);
+                    compile_file(&source_map, file, error_format, &script_args);
                 }
                 Err(e) => {
                     eprintln!("Error: Failed to read the file: {}", e);
@@ -44,7 +98,7 @@ fn main() {
             }
         }
         (Some(_), _) => {
-            eprintln!("Error: Only files with a .rsc extension are allowed.");
+            eprintln!("Error: Only files with a .rsc extension or no extension are allowed.");
             std::process::exit(1);
         }
         _ => {
@@ -53,3 +107,107 @@ fn main() {
         }
     }
 }
+
+// Lexes the file a chunk at a time, printing its diagnostics, and exits
+// with failure if there are errors
+fn check(path: &Path, error_format: ErrorFormat) -> ! {
+    let file = match fs::File::open(path) {
+        Ok(file) => file,
+        Err(e) => {
+            eprintln!("Error: Failed to read the file: {}", e);
+            std::process::exit(1);
+        }
+    };
+    let mut lexer = StreamLexer::new(file, FileId::default());
+    let mut errors = 0;
+    loop {
+        let token = match lexer.next_token() {
+            Ok(token) => token,
+            Err(e) => {
+                eprintln!("Error: Failed to read the file: {}", e);
+                std::process::exit(1);
+            }
+        };
+        let diagnostics = lexer.take_diagnostics();
+        errors += diagnostics
+            .iter()
+            .filter(|diagnostic| diagnostic.severity == Severity::Error)
+            .count();
+        match error_format {
+            ErrorFormat::Json => {
+                let checked = Checked {
+                    name: &path.to_string_lossy(),
+                    lexer: &lexer,
+                };
+                let mut emitter = JsonEmitter::new(&checked, io::stderr());
+                for diagnostic in &diagnostics {
+                    emitter.emit(diagnostic);
+                }
+            }
+            ErrorFormat::Human => {
+                for diagnostic in &diagnostics {
+                    print_checked(path, &lexer, diagnostic);
+                }
+            }
+        }
+        if token.kind == TokenKind::Eof {
+            break;
+        }
+    }
+    std::process::exit((errors > 0) as i32);
+}
+
+// Prints a diagnostic of check on one line of stdout
+fn print_checked<R: Read>(path: &Path, lexer: &StreamLexer<R>, diagnostic: &Diagnostic) {
+    let severity = match diagnostic.severity {
+        Severity::Error => "error",
+        Severity::Warning => "warning",
+    };
+    // the line of an unterminated "${" may be gone already
+    let position = match lexer.location(diagnostic.primary.span.low) {
+        Some(location) => format!(":{}:{}", location.line + 1, location.column + 1),
+        None => String::new(),
+    };
+    println!(
+        "{}{}: {}[{}]: {}",
+        path.display(),
+        position,
+        severity,
+        diagnostic.code,
+        diagnostic.message.replace('`', "")
+    );
+}
+
+// Locations in a file being checked, as far as its lexer still has them
+struct Checked<'a, R: Read> {
+    name: &'a str,
+    lexer: &'a StreamLexer<R>,
+}
+
+impl<R: Read> SpanLocator for Checked<'_, R> {
+    fn file_name(&self, _: FileId) -> &str {
+        self.name
+    }
+
+    fn locate(&self, _: FileId, offset: usize) -> Option<Location> {
+        self.lexer.location(offset)
+    }
+}
+
+// Prints the long explanation of an error code, e.g. E0003, and exits
+fn explain(code: Option<&str>, usage: &str) -> ! {
+    let Some(code) = code else {
+        eprintln!("{}", usage);
+        std::process::exit(1);
+    };
+    match ErrorCode::parse(code) {
+        Some(code) => {
+            println!("{}", code.explain());
+            std::process::exit(0);
+        }
+        None => {
+            eprintln!("Error: `{}` is not a valid error code.", code);
+            std::process::exit(1);
+        }
+    }
+}
@@ -0,0 +1,69 @@
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use std::process::{Command, Output};
+
+// Writes a file to run the repl on, a script is installed without extension
+fn script(name: &str, source: &str) -> PathBuf {
+    let dir = env::temp_dir().join(format!("repl-test-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let path = dir.join(name);
+    fs::write(&path, source).unwrap();
+    path
+}
+
+fn repl(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_repl"))
+        .args(args)
+        .output()
+        .unwrap()
+}
+
+#[test]
+fn shebang_script_runs_with_arguments() {
+    let path = script("tool", "#!/usr/bin/env repl\nlet a = 1;\n");
+    let path = path.to_str().unwrap();
+
+    let output = repl(&[path, "foo", "--bar", "-x"]);
+    assert!(output.status.success(), "{:?}", output);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("File name: tool"), "{}", stdout);
+
+    // options before the path are still the interpreter's
+    let output = repl(&["--error-format=json", path, "foo"]);
+    assert!(output.status.success(), "{:?}", output);
+}
+
+#[test]
+fn fix_and_check_reject_extra_arguments() {
+    let path = script("checked", "let a = 1;\n");
+    let path = path.to_str().unwrap();
+
+    for command in ["fix", "check"] {
+        let output = repl(&[command, path, "foo"]);
+        assert_eq!(output.status.code(), Some(1), "{:?}", output);
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert!(stderr.starts_with("Usage:"), "{}", stderr);
+    }
+}
+
+#[test]
+fn check_reports_json_when_asked() {
+    let path = script("escape.rsc", "let s = \"a\\q\";\n");
+    let path = path.to_str().unwrap();
+
+    let output = repl(&["check", "--error-format=json", path]);
+    assert_eq!(output.status.code(), Some(1), "{:?}", output);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.starts_with("{\"code\":12,\"severity\":\"error\","),
+        "{}",
+        stderr
+    );
+    assert!(stderr.contains("\"line_start\":1,\"column_start\":11,"));
+    assert!(output.stdout.is_empty());
+
+    let output = repl(&["check", path]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.ends_with(":1:11: error[E0012]: Invalid Escape Sequence\n"));
+}
@@ -0,0 +1,384 @@
+use compiler::{
+    tokenize, tokenize_lossless, Diagnostic, KeywordKind, Severity, SyntaxToken, TemplatePart,
+    TokenKind,
+};
+
+const INDENT: &str = "    ";
+
+// blank lines kept between two lines of code, the rest are dropped
+const MAX_BLANK_LINES: usize = 1;
+
+/// Formats a source file, or returns its errors if it does not lex
+///
+/// Only whitespace is changed: line breaks are kept but blank lines are
+/// collapsed, lines are indented by their open delimiters, spacing between
+/// tokens is normalised and an opening brace joins the line before it.
+/// Formatting formatted code returns it unchanged.
+pub fn format(source: &str) -> Result<String, Vec<Diagnostic>> {
+    let (tokens, diagnostics) = tokenize_lossless(source);
+    let errors: Vec<Diagnostic> = diagnostics
+        .into_iter()
+        .filter(|diagnostic| diagnostic.severity == Severity::Error)
+        .collect();
+    if !errors.is_empty() {
+        return Err(errors);
+    }
+    Ok(Formatter::default().format(&items(&tokens)))
+}
+
+/// Token or comment, with the whitespace found before it in the source
+struct Item<'a> {
+    kind: TokenKind<'a>,
+    text: &'a str,
+    newlines: usize,
+    spaced: bool,
+}
+
+#[derive(Default)]
+struct Items<'a> {
+    items: Vec<Item<'a>>,
+    // whitespace seen since the last item
+    newlines: usize,
+    spaced: bool,
+}
+
+impl<'a> Items<'a> {
+    fn push(&mut self, kind: &TokenKind<'a>, text: &'a str) {
+        if *kind == TokenKind::Whitespace {
+            self.newlines += text.matches('\n').count();
+            self.spaced = true;
+            return;
+        }
+        self.items.push(Item {
+            kind: kind.clone(),
+            text,
+            newlines: self.newlines,
+            spaced: self.spaced,
+        });
+        (self.newlines, self.spaced) = (0, false);
+    }
+}
+
+// Flattens the tokens and their trivia into everything but whitespace and Eof
+fn items<'a>(tokens: &[SyntaxToken<'a>]) -> Vec<Item<'a>> {
+    let mut items = Items::default();
+    for token in tokens {
+        for trivia in &token.leading {
+            items.push(&trivia.kind, trivia.text);
+        }
+        if token.kind != TokenKind::Eof {
+            items.push(&token.kind, token.text);
+        }
+        for trivia in &token.trailing {
+            items.push(&trivia.kind, trivia.text);
+        }
+    }
+    items.items
+}
+
+#[derive(Default)]
+struct Formatter {
+    out: String,
+    // indentation of the line each open delimiter is on
+    open: Vec<usize>,
+    // indentation of the current line
+    line_indent: usize,
+}
+
+impl Formatter {
+    fn format(mut self, items: &[Item]) -> String {
+        if items.is_empty() {
+            return String::new();
+        }
+        let mut previous: Option<&Item> = None;
+        // last token before the previous item, to tell unary operators apart
+        let mut before: Option<&TokenKind> = None;
+        let mut code: Option<&TokenKind> = None;
+        for (index, item) in items.iter().enumerate() {
+            let closes = closes(&item.kind);
+            match previous {
+                None => {}
+                Some(previous) if self.breaks_line(previous, item) => {
+                    let lines = match opens(&previous.kind) || closes {
+                        true => 1,
+                        false => item.newlines.clamp(1, MAX_BLANK_LINES + 1),
+                    };
+                    self.newlines(lines);
+                    self.line_indent = match closes {
+                        true => self.open.last().copied().unwrap_or(0),
+                        false => self.open.last().map_or(0, |indent| indent + 1),
+                    };
+                    self.out.push_str(&INDENT.repeat(self.line_indent));
+                }
+                Some(previous)
+                    if spaced(before, previous, item)
+                        || glued(previous, item, items.get(index + 1)) =>
+                {
+                    self.out.push(' ');
+                }
+                Some(_) => {}
+            }
+            if closes {
+                self.open.pop();
+            }
+            if opens(&item.kind) {
+                self.open.push(self.line_indent);
+            }
+            self.out.push_str(item.text);
+            previous = Some(item);
+            if !is_comment(&item.kind) {
+                before = code;
+                code = Some(&item.kind);
+            }
+        }
+        self.newlines(1);
+        self.out
+    }
+
+    fn breaks_line(&self, previous: &Item, item: &Item) -> bool {
+        // an opening brace joins the line of its header
+        let joins = item.kind == TokenKind::OpenBrace
+            && !is_comment(&previous.kind)
+            && !matches!(
+                previous.kind,
+                TokenKind::Semi | TokenKind::Comma | TokenKind::OpenBrace | TokenKind::CloseBrace
+            );
+        let after_line_comment = matches!(
+            previous.kind,
+            TokenKind::Shebang | TokenKind::LineComment | TokenKind::LineDocComment { .. }
+        );
+        after_line_comment || (item.newlines > 0 && !joins)
+    }
+
+    // ends the line with the given number of line breaks, dropping trailing spaces
+    fn newlines(&mut self, lines: usize) {
+        let trimmed = self.out.trim_end_matches([' ', '\t']).len();
+        self.out.truncate(trimmed);
+        self.out.push_str(&"\n".repeat(lines));
+    }
+}
+
+fn opens(kind: &TokenKind) -> bool {
+    matches!(
+        kind,
+        TokenKind::OpenBrace
+            | TokenKind::OpenPara
+            | TokenKind::OpenBracket
+            | TokenKind::Template {
+                part: TemplatePart::Head | TemplatePart::Middle,
+                ..
+            }
+    )
+}
+
+fn closes(kind: &TokenKind) -> bool {
+    matches!(
+        kind,
+        TokenKind::CloseBrace
+            | TokenKind::ClosePara
+            | TokenKind::CloseBracket
+            | TokenKind::Template {
+                part: TemplatePart::Middle | TemplatePart::Tail,
+                ..
+            }
+    )
+}
+
+// the shebang is laid out like a line comment
+fn is_comment(kind: &TokenKind) -> bool {
+    matches!(
+        kind,
+        TokenKind::Shebang
+            | TokenKind::LineComment
+            | TokenKind::BlockComment { .. }
+            | TokenKind::LineDocComment { .. }
+            | TokenKind::BlockDocComment { .. }
+    )
+}
+
+fn is_binary(kind: &TokenKind) -> bool {
+    use TokenKind::*;
+    matches!(
+        kind,
+        Arrow
+            | Or
+            | And
+            | NotEq
+            | EqEq
+            | GtEq
+            | LtEq
+            | PlusEq
+            | MinusEq
+            | AsteriskEq
+            | SlashEq
+            | PercentEq
+            | CaretEq
+            | AmpersandEq
+            | PipeEq
+            | ShlEq
+            | ShrEq
+            | Power
+            | Shl
+            | Shr
+            | FatArrow
+            | Coalesce
+            | PipeGt
+            | Eq
+            | Plus
+            | Minus
+            | Asterisk
+            | Slash
+            | Percent
+            | Caret
+            | Ampersand
+            | Lt
+            | Gt
+    )
+}
+
+// whether an operator which may be unary is unary after the given token
+fn is_unary(kind: &TokenKind, code: Option<&TokenKind>) -> bool {
+    use TokenKind::*;
+    matches!(kind, Minus | Plus | Bang | Tilde)
+        && code.is_none_or(|code| {
+            is_binary(code)
+                || matches!(
+                    code,
+                    OpenPara
+                        | OpenBracket
+                        | OpenBrace
+                        | Comma
+                        | Semi
+                        | Colon
+                        | Question
+                        | Bang
+                        | Tilde
+                        | Template {
+                            part: TemplatePart::Head | TemplatePart::Middle,
+                            ..
+                        }
+                )
+                || matches!(code, Keyword { kind } if !is_value_keyword(*kind))
+        })
+}
+
+// keywords used like values or types rather than to start a construct
+fn is_value_keyword(kind: KeywordKind) -> bool {
+    use KeywordKind::*;
+    matches!(
+        kind,
+        True | False | IntegerType | FloatType | StringType | BooleanType
+    )
+}
+
+/// Whether one space goes between two items on the same line, before is
+/// the last token ahead of the previous one
+fn spaced(before: Option<&TokenKind>, previous: &Item, item: &Item) -> bool {
+    use TokenKind::*;
+    match (&previous.kind, &item.kind) {
+        // comments keep their distance from code
+        (kind, _) | (_, kind) if is_comment(kind) => true,
+        // inside template strings the source is taken as is
+        (
+            Template {
+                part: TemplatePart::Head | TemplatePart::Middle,
+                ..
+            },
+            _,
+        )
+        | (
+            _,
+            Template {
+                part: TemplatePart::Middle | TemplatePart::Tail,
+                ..
+            },
+        ) => false,
+        (_, Comma | Semi | Dot | OptionalDot | Scope | Colon) => false,
+        (Dot | OptionalDot | Scope | Ellipsis, _) => false,
+        (Semi, ClosePara) => false,
+        (Comma | Semi | Colon, _) => true,
+        (OpenPara | OpenBracket, _) | (_, ClosePara | CloseBracket) => false,
+        (OpenBrace, CloseBrace) => false,
+        (OpenBrace, _) | (_, CloseBrace | OpenBrace) => true,
+        (kind, _) if is_unary(kind, before) => false,
+        (_, kind) if is_unary(kind, Some(&previous.kind)) => true,
+        (kind, _) | (_, kind) if is_binary(kind) => true,
+        // "++" and "--" stick to their operand on either side
+        (Increment | Decrement, _) | (_, Increment | Decrement) => false,
+        // calls and indexing
+        (Keyword { kind }, OpenPara | OpenBracket) => {
+            !matches!(kind, KeywordKind::Fn | KeywordKind::Function) && !is_value_keyword(*kind)
+        }
+        (
+            Ident { .. }
+            | Literal { .. }
+            | ClosePara
+            | CloseBracket
+            | CloseBrace
+            | Template {
+                part: TemplatePart::Full | TemplatePart::Tail,
+                ..
+            },
+            OpenPara | OpenBracket,
+        ) => false,
+        (
+            Ident { .. } | Keyword { .. } | Literal { .. } | ClosePara | CloseBracket | CloseBrace,
+            Ident { .. } | Keyword { .. } | Literal { .. },
+        ) => true,
+        // anything else keeps whether it was spaced
+        _ => item.spaced,
+    }
+}
+
+/// Whether two tokens written without a space would lex as something else
+///
+/// next is the item after them, "5" and "." lex apart only when a name
+/// follows, and nothing but a line break separates a '.' from that name.
+fn glued(previous: &Item, item: &Item, next: Option<&Item>) -> bool {
+    let template = |kind: &TokenKind| matches!(kind, TokenKind::Template { .. });
+    if template(&previous.kind) || template(&item.kind) || is_comment(&previous.kind) {
+        return false;
+    }
+    let length = previous.text.chars().count() + item.text.chars().count();
+    let breaks = |after: &str| {
+        let joined = format!("{}{}{}", previous.text, item.text, after);
+        let (tokens, _) = tokenize(&joined);
+        match tokens.as_slice() {
+            [first, second, ..] => {
+                first.kind != previous.kind
+                    || second.kind != item.kind
+                    || second.span.high != length
+            }
+            _ => true,
+        }
+    };
+    match next {
+        Some(next) if next.newlines == 0 && !template(&next.kind) && !is_comment(&next.kind) => {
+            breaks("") && breaks(next.text)
+        }
+        _ => breaks(""),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::format;
+
+    #[test]
+    fn formatting_is_idempotent() {
+        let sources = [
+            "",
+            "#!/usr/bin/env rsc\nlet a=1;\n",
+            "let a = 1;\r\n\r\n\r\n// comment\r\nif a{\r\nb(a,1)\r\n}\r\n",
+            "//! module docs\n/// item docs\nfn f( a,b ){\n/** block docs */\nreturn a+b ;}\n",
+            "let s = `a ${b+`c ${ d } e`} ${ {f} } g`;\nlet t=`${`${`x`}`}`;\n",
+            "fn f()\n{\n    let x = [1,\n2,\n   3];   // trailing\n\n\n\n  x.len() /* nested /* block */ */\n}\n",
+            "match x {\n1..=2 => a?.b,\n_ => !c && -d,\n}\n",
+        ];
+        for source in sources {
+            let once = format(source).unwrap();
+            let twice = format(&once).unwrap();
+            assert_eq!(twice, once, "{:?}", source);
+        }
+    }
+}
@@ -0,0 +1,98 @@
+mod format;
+
+use std::env;
+use std::fs;
+use std::process::ExitCode;
+
+use compiler::{Diagnostic, SourceMap};
+
+fn main() -> ExitCode {
+    let args: Vec<String> = env::args().collect();
+    let usage = format!("Usage: {} [--check] <file_path>...", args[0]);
+
+    // Split the options from the file paths
+    let mut check = false;
+    let mut paths = Vec::new();
+    for arg in &args[1..] {
+        match arg.as_str() {
+            "--check" => check = true,
+            option if option.starts_with("--") => {
+                eprintln!("Error: Unknown option `{}`.\n{}", option, usage);
+                return ExitCode::FAILURE;
+            }
+            path => paths.push(path),
+        }
+    }
+    if paths.is_empty() {
+        eprintln!("{}", usage);
+        return ExitCode::FAILURE;
+    }
+
+    let mut failed = false;
+    for path in paths {
+        failed |= !format_file(path, check);
+    }
+    match failed {
+        true => ExitCode::FAILURE,
+        false => ExitCode::SUCCESS,
+    }
+}
+
+// Formats one file in place, or only reports whether it is formatted when
+// checking, returning false if the file is not fine
+fn format_file(path: &str, check: bool) -> bool {
+    let source = match fs::read_to_string(path) {
+        Ok(source) => source,
+        Err(e) => {
+            eprintln!("Error: Failed to read {}: {}", path, e);
+            return false;
+        }
+    };
+    let formatted = match format::format(&source) {
+        Ok(formatted) => formatted,
+        Err(diagnostics) => {
+            report(path, &source, &diagnostics);
+            return false;
+        }
+    };
+    // formatting the output again must not change it, otherwise the
+    // formatter has a bug and the file is left alone
+    if format::format(&formatted).as_ref() != Ok(&formatted) {
+        eprintln!(
+            "Error: Formatting {} is not stable, the file was not changed",
+            path
+        );
+        return false;
+    }
+    if formatted == source {
+        return true;
+    }
+    if check {
+        println!("Would reformat: {}", path);
+        return false;
+    }
+    match fs::write(path, formatted) {
+        Ok(()) => true,
+        Err(e) => {
+            eprintln!("Error: Failed to write {}: {}", path, e);
+            false
+        }
+    }
+}
+
+// Files which do not lex are not formatted, prints why
+fn report(path: &str, source: &str, diagnostics: &[Diagnostic]) {
+    let mut source_map = SourceMap::new();
+    let file = source_map.add(path, source);
+    for diagnostic in diagnostics {
+        let location = source_map.location(file, diagnostic.primary.span.low);
+        eprintln!(
+            "{}:{}:{}: error[{}]: {}",
+            path,
+            location.line + 1,
+            location.column + 1,
+            diagnostic.code,
+            diagnostic.message.replace('`', "")
+        );
+    }
+}